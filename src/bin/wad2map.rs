@@ -2,68 +2,103 @@
 
 extern crate wad2map;
 
+use std::cmp::{max, min};
 use std::process::exit;
 
 use wad2map::optparse::Options;
-use wad2map::parse_wad::parse_wad;
-use wad2map::mapmaker::make_maps_from_wad;
+use wad2map::commands::run_command;
+use wad2map::pool;
+use wad2map::inputs::expand;
+use wad2map::watch::watch;
+use wad2map::report::{Report, FileReport};
+
+
+// exit codes, so scripts can tell how a run went
+const EXIT_OK      : i32 = 0; // every file went through
+const EXIT_FAILED  : i32 = 1; // every file failed
+const EXIT_USAGE   : i32 = 2; // the command line couldn't be used
+const EXIT_PARTIAL : i32 = 3; // some files failed and some didn't
+
 
 fn main() {
     // generate an Options struct reading args from CLI
     let opts = match Options::new() {
         Ok(opt) => opt,
         Err(ec) => {
-            println!("{}", ec);
-            exit(-2);
+            eprintln!("{}", ec);
+            exit(EXIT_USAGE);
         }
     };
 
     // do a bunch of checks and then exit with status codes
     if opts.help {
         opts.print_help();
-        exit(0);
+        exit(EXIT_OK);
     }
 
     if opts.version {
         opts.print_version();
-        exit(0);
+        exit(EXIT_OK);
     }
 
-    if opts.files.len() == 0 {
-        println!("No files supplied");
-        exit(-1);
+    if opts.files.is_empty() {
+        eprintln!("No files supplied");
+        exit(EXIT_USAGE);
     }
 
-    // loop through all arguments and parse a wad from each one
-    let mut passes : usize = 0;
-    let mut fails  : usize = 0;
-    for file in &opts.files {
-        let fname = format!("{}", file).to_owned();
-
-        match parse_wad(&fname, &opts) {
-            Ok(new_wad) => match make_maps_from_wad(&fname, &new_wad, &opts) {
-                Ok(_) => { passes += 1; },
-                Err(e) => {
-                    if opts.verbose {
-                        println!("make_maps_from_svg: {}", e);
-                    }
-                    fails += 1;
-                }
-            },
-            Err(e) => {
-                if opts.verbose {
-                    println!("parse_wad: {}", e);
-                }
-                fails += 1;
-            }
-        }
+    // directories given are searched for the files inside them, and any
+    // that can't be read count as files that failed
+    let (inputs, unreadable) = expand(&opts);
+    if opts.to_stdout() && inputs.len() > 1 {
+        eprintln!("Err: --output - writes to stdout for a single file only");
+        exit(EXIT_USAGE);
+    }
+    let mut report = Report::default();
+    for (dir, why) in unreadable {
+        eprintln!("{}: {}", dir, why);
+        let mut dir_report = FileReport::new(&dir);
+        dir_report.errors.push(why);
+        report.files.push(dir_report);
     }
 
+    // files are worked on --jobs at a time, and whatever workers are left
+    // over go to the levels of each file
+    let files = max(1, min(opts.jobs, inputs.len()));
+    let mut inner = opts.clone();
+    inner.jobs = max(1, opts.jobs / files);
+    inner.file_count = inputs.len();
+
+    pool::run(opts.jobs, &inputs, |input| {
+        let mut file_report = FileReport::new(&input.path);
+        if let Err(e) = run_command(&input.path, &input.options(&inner), &mut file_report) {
+            file_report.errors.push(e);
+        }
+        file_report
+    }, |input, file_report| {
+        for e in &file_report.errors {
+            eprintln!("{}: {}", input.path, e);
+        }
+        report.files.push(file_report);
+    });
+
+    let (passes, fails) = (report.passed(), report.failed());
     if opts.verbose {
-        println!("{} file(s) rendered, {} file(s) failed", passes, fails);
+        println!("{} file(s) passed, {} file(s) failed", passes, fails);
+    }
+    if opts.report {
+        println!("{}", report.to_json().pretty());
+    }
+
+    // keep rendering changes until stopped
+    if opts.watch {
+        watch(&inputs, &opts);
     }
 
-    exit(0);
+    exit(match (passes, fails) {
+        (_, 0) => EXIT_OK,
+        (0, _) => EXIT_FAILED,
+        _      => EXIT_PARTIAL,
+    });
 }
 
 // end
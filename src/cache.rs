@@ -0,0 +1,135 @@
+// cache.rs
+
+/// The render cache. Every output directory keeps a manifest of the files
+/// written into it and the hash of what went into each one, the level's
+/// lumps and the options it was drawn with. When a level comes up again
+/// with the same hash and its file is still there, it doesn't need to be
+/// drawn again; --force draws everything regardless.
+///
+/// A manifest is a text file with a line for each file, its hash and then
+/// its name, and is replaced whole whenever it changes.
+
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, rename, write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+
+pub const MANIFEST_NAME: &str = ".wad2map-manifest";
+const MANIFEST_HEAD: &str     = "# wad2map manifest 1";
+
+
+// held while a manifest is read, changed and written back, since levels
+// and files finishing on other threads may share an output directory
+static WRITING: Mutex<()> = Mutex::new(());
+
+
+pub struct Manifest {
+    path:    PathBuf,
+    entries: BTreeMap<String, String>, // file name to hash
+}
+
+
+impl Manifest {
+    // the manifest of a directory, empty if it has none or it can't be read
+    pub fn load(dir: &Path) -> Manifest {
+        let path = dir.join(MANIFEST_NAME);
+        let mut entries = BTreeMap::new();
+        if let Ok(text) = read_to_string(&path) {
+            for line in text.lines().filter(|l| !l.starts_with('#')) {
+                if let Some((hash, name)) = line.split_once(' ') {
+                    entries.insert(name.to_owned(), hash.to_owned());
+                }
+            }
+        }
+        Manifest{ path: path, entries: entries }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(|h| h.as_str())
+    }
+
+    pub fn set(&mut self, name: &str, hash: &str) {
+        self.entries.insert(name.to_owned(), hash.to_owned());
+    }
+
+    // write the manifest out beside itself and move it into place, so a
+    // reader never sees half of one
+    pub fn save(&self) -> Result<u8, String> {
+        let mut text = format!("{}\n", MANIFEST_HEAD);
+        for (name, hash) in &self.entries {
+            text.push_str(&format!("{} {}\n", hash, name));
+        }
+
+        let fresh = self.path.with_extension("new");
+        if let Err(why) = write(&fresh, text).and_then(|_| rename(&fresh, &self.path)) {
+            return Err(format!("Couldn't write '{}': {}", self.path.display(), why));
+        }
+        Ok(0)
+    }
+}
+
+
+// split a file's path into its directory and name in that directory
+fn split(path: &Path) -> (PathBuf, String) {
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _                                    => PathBuf::from("."),
+    };
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    (dir, name)
+}
+
+
+// whether a file is there and was last written from the same hash
+pub fn is_fresh(path: &Path, hash: &str) -> bool {
+    let (dir, name) = split(path);
+    path.exists() && Manifest::load(&dir).get(&name) == Some(hash)
+}
+
+
+// record the hashes of files that were just written, one manifest per directory
+pub fn record(written: &[(PathBuf, String)]) -> Result<u8, String> {
+    let mut by_dir: BTreeMap<PathBuf, Vec<(String, &str)>> = BTreeMap::new();
+    for (path, hash) in written {
+        let (dir, name) = split(path);
+        by_dir.entry(dir).or_default().push((name, hash));
+    }
+
+    let _held = WRITING.lock().unwrap_or_else(|e| e.into_inner());
+    for (dir, files) in by_dir {
+        let mut manifest = Manifest::load(&dir);
+        for (name, hash) in files {
+            manifest.set(&name, hash);
+        }
+        manifest.save()?;
+    }
+    Ok(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use cache::*;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_manifest() {
+        let dir = temp_dir().join("wad2map-test-manifest");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let map = dir.join("MAP01.svg");
+        write(&map, "<svg/>").unwrap();
+
+        assert!(!is_fresh(&map, "0123"));
+        record(&[(map.clone(), String::from("0123"))]).unwrap();
+        assert!(is_fresh(&map, "0123"));
+        assert!(!is_fresh(&map, "4567"));
+        assert!(!is_fresh(&dir.join("MAP02.svg"), "0123"));
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
+
+// end
@@ -0,0 +1,283 @@
+// commands.rs
+
+/// Every command the binary can run on a WAD. Rendering is handed off to
+/// the mapmaker and exporting to the exporter, while the others (info,
+/// list, extract, check and stats) look inside the WAD and report on
+/// what they find.
+
+use std::collections::HashMap;
+use std::fs::{create_dir_all, write};
+
+use optparse::{Options, Command};
+use parse_wad::{WadFile, read_wad_file, build_wad, parse_wad};
+use mapmaker::make_maps_from_wad;
+use export::export_wad;
+use mapfilter::select_levels;
+use report::FileReport;
+use output::lump_file_name;
+use doom::wad::MAP_LUMPS;
+use doom::constants::{DOOM_THING_WIDTH, HEXEN_THING_WIDTH, DOOM_LINEDEF_WIDTH, HEXEN_LINEDEF_WIDTH};
+use doom::constants::{SIDEDEF_WIDTH, VERTEX_WIDTH, SECTOR_WIDTH};
+
+
+/// Lumps every level needs, with the width of one record in each
+/// (Doom's width first, then Hexen's)
+const LEVEL_LUMPS: &[(&str, usize, usize)] = &[
+    ("THINGS",   DOOM_THING_WIDTH,   HEXEN_THING_WIDTH),
+    ("LINEDEFS", DOOM_LINEDEF_WIDTH, HEXEN_LINEDEF_WIDTH),
+    ("SIDEDEFS", SIDEDEF_WIDTH,      SIDEDEF_WIDTH),
+    ("VERTEXES", VERTEX_WIDTH,       VERTEX_WIDTH),
+    ("SECTORS",  SECTOR_WIDTH,       SECTOR_WIDTH),
+];
+
+
+// Run whichever command was asked for on one WAD, noting what it did
+// in the file's report
+pub fn run_command(fname: &str, opts: &Options, report: &mut FileReport) -> Result<u8, String> {
+    match opts.command.unwrap_or(Command::Render) {
+        Command::Render => {
+            let wad = parse_wad(fname, opts)?;
+            make_maps_from_wad(fname, &wad, opts, report)
+        }
+        Command::Info    => info(fname, opts),
+        Command::List    => list(fname, opts),
+        Command::Extract => extract(fname, opts),
+        Command::Check   => check(fname, opts, report),
+        Command::Stats   => stats(fname, opts),
+        Command::Export  => {
+            let wad = parse_wad(fname, opts)?;
+            export_wad(fname, &wad, opts, report)
+        }
+    }
+}
+
+
+// name the file being reported on when there's more than one
+fn heading(fname: &str, opts: &Options) {
+    if opts.file_count > 1 {
+        say!("{}:", fname);
+    }
+}
+
+
+// the level each lump belongs to, if it's one of a level's map lumps
+// a level's lumps follow its marker until one that isn't a map lump
+fn lump_levels(file: &WadFile) -> Vec<Option<&str>> {
+    let mut level: Option<&str> = None;
+    file.lumps
+        .iter()
+        .map(|lump| {
+            if lump.is_level {
+                level = Some(&lump.name);
+                return None;
+            }
+            if !MAP_LUMPS.contains(&lump.name.as_str()) {
+                level = None;
+            }
+            level
+        })
+        .collect()
+}
+
+
+fn info(fname: &str, opts: &Options) -> Result<u8, String> {
+    let wad = parse_wad(fname, opts)?;
+    wad.print_info();
+    wad.header.print();
+    if opts.verbose {
+        wad.print_level_info();
+    }
+    Ok(0)
+}
+
+
+fn list(fname: &str, opts: &Options) -> Result<u8, String> {
+    let both = !opts.list_lumps && !opts.list_levels;
+    let file = read_wad_file(fname, opts)?;
+    heading(fname, opts);
+
+    if opts.list_lumps || both {
+        for lump in &file.lumps {
+            lump.print();
+        }
+    }
+
+    if opts.list_levels || both {
+        let wad = build_wad(fname, file, opts)?;
+        for lev in select_levels(&wad.levels, &opts.maps, fname) {
+            match wad.titles.get(&lev.name) {
+                Some(title) => say!("{:<8} {}", lev.name, title),
+                None        => say!("{}", lev.name),
+            }
+        }
+    }
+    Ok(0)
+}
+
+
+fn extract(fname: &str, opts: &Options) -> Result<u8, String> {
+    let file = read_wad_file(fname, opts)?;
+    let dir = match opts.extract_dir {
+        Some(ref d) => d.to_owned(),
+        None        => format!("{}.lumps", fname),
+    };
+
+    let mut written: HashMap<String, usize> = HashMap::new();
+    for (lump, level) in file.lumps.iter().zip(lump_levels(&file)) {
+        // markers carry no data, so there's nothing to write for them
+        if lump.size == 0 {
+            continue;
+        }
+        if !opts.extract.is_empty() && !opts.extract.contains(&lump.name) {
+            continue;
+        }
+
+        let data = match file.lump_data(lump) {
+            Some(d) => d,
+            None    => { return Err(format!("Lump {} lies outside of the Wad", lump.name)); }
+        };
+
+        let folder = match level {
+            Some(l) => format!("{}/{}", dir, lump_file_name(l)),
+            None    => dir.to_owned(),
+        };
+        if let Err(why) = create_dir_all(&folder) {
+            return Err(format!("Couldn't create '{}': {}", folder, why));
+        }
+
+        // lumps can share a name, so later ones get numbered
+        let base = format!("{}/{}", folder, lump_file_name(&lump.name));
+        let count = written.entry(base.to_owned()).or_insert(0);
+        let path = match *count {
+            0 => format!("{}.lmp", base),
+            n => format!("{}.{}.lmp", base, n),
+        };
+        *count += 1;
+
+        if let Err(why) = write(&path, data) {
+            return Err(format!("Couldn't write '{}': {}", path, why));
+        }
+        if opts.verbose {
+            say!("Wrote {} ({} bytes)", path, data.len());
+        }
+    }
+
+    if opts.verbose {
+        say!("Extracted {} lump(s) into {}", written.values().sum::<usize>(), dir);
+    }
+    Ok(0)
+}
+
+
+// every problem found is an error in the report
+fn check(fname: &str, opts: &Options, report: &mut FileReport) -> Result<u8, String> {
+    let file = read_wad_file(fname, opts)?;
+    let is_hexen = file.is_hexen();
+    let mut problems: Vec<String> = Vec::new();
+
+    for lump in &file.lumps {
+        if file.lump_data(lump).is_none() {
+            problems.push(format!("lump {} lies outside of the file", lump.name));
+        }
+    }
+
+    // every level needs its map lumps, each a whole number of records long
+    let levels = lump_levels(&file);
+    for (i, marker) in file.lumps.iter().enumerate() {
+        let owned: Vec<_> = file.lumps
+            .iter()
+            .zip(&levels)
+            .skip(i + 1)
+            .take_while(|&(_, level)| level.is_some())
+            .map(|(lump, _)| lump)
+            .collect();
+        if !marker.is_level || owned.is_empty() {
+            continue;
+        }
+
+        for &(name, doom_width, hexen_width) in LEVEL_LUMPS {
+            let width = match is_hexen { true => hexen_width, _ => doom_width };
+            match owned.iter().find(|l| l.name == name) {
+                None => {
+                    problems.push(format!("{} has no {} lump", marker.name, name));
+                }
+                Some(l) if l.size % width != 0 => {
+                    problems.push(format!(
+                        "{} {} lump has {} stray byte(s)", marker.name, name, l.size % width,
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    match build_wad(fname, file, opts) {
+        Ok(wad) => {
+            for lev in &wad.levels {
+                for p in lev.problems() {
+                    problems.push(format!("{}: {}", lev.name, p));
+                }
+            }
+        }
+        // a lump outside of the file stops the levels being read, and
+        // has already been reported above
+        Err(e) => {
+            if problems.is_empty() {
+                problems.push(e);
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        say!("{}: no problems found", fname);
+    }
+    report.errors.extend(problems);
+    Ok(0)
+}
+
+
+fn stats(fname: &str, opts: &Options) -> Result<u8, String> {
+    let wad = parse_wad(fname, opts)?;
+    let levels = select_levels(&wad.levels, &opts.maps, fname);
+    heading(fname, opts);
+
+    say!(
+        "{:<8} {:>7} {:>8} {:>8} {:>8} {:>7} {:>8} {:>7} {:>11}",
+        "Level", "Things", "Vertices", "Linedefs", "Sidedefs", "Sectors", "Specials", "Secrets", "Size",
+    );
+
+    let mut totals = [0usize; 7];
+    for lev in &levels {
+        let counts = [
+            lev.things.len(),
+            lev.vertices.len(),
+            lev.linedefs.len(),
+            lev.sidedefs.len(),
+            lev.sectors.len(),
+            lev.linedefs.iter().filter(|l| l.special_type() != 0).count(),
+            lev.sectors.iter().filter(|s| s.is_secret(wad.is_hexen)).count(),
+        ];
+        for (total, n) in totals.iter_mut().zip(counts.iter()) {
+            *total += n;
+        }
+
+        let size = match lev.bounds() {
+            Some((x1, y1, x2, y2)) => format!("{}x{}", x2 as i32 - x1 as i32, y2 as i32 - y1 as i32),
+            None                   => String::from("-"),
+        };
+        say!(
+            "{:<8} {:>7} {:>8} {:>8} {:>8} {:>7} {:>8} {:>7} {:>11}",
+            lev.name, counts[0], counts[1], counts[2], counts[3], counts[4], counts[5], counts[6], size,
+        );
+    }
+
+    if levels.len() > 1 {
+        say!(
+            "{:<8} {:>7} {:>8} {:>8} {:>8} {:>7} {:>8} {:>7}",
+            "Total", totals[0], totals[1], totals[2], totals[3], totals[4], totals[5], totals[6],
+        );
+    }
+    Ok(0)
+}
+
+// end
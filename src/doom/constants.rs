@@ -12,6 +12,14 @@ pub const SSECTOR_WIDTH       : usize =  4;
 pub const SIDEDEF_WIDTH       : usize = 30;
 pub const DOOM_LINEDEF_WIDTH  : usize = 14;
 pub const HEXEN_LINEDEF_WIDTH : usize = 16;
+pub const DOOM_THING_WIDTH    : usize = 10;
+pub const HEXEN_THING_WIDTH   : usize = 20;
+
+/// LineDef flag bits shared by the Doom and Hexen formats
+/// Only the ones that change how a level is drawn are listed here
+pub const ML_SECRET           : u16 = 0x0020;
+pub const ML_DONTDRAW         : u16 = 0x0080;
+pub const ML_MAPPED           : u16 = 0x0100;
 
 /// These numbers are used in determining the type of Wad that we are given.
 /// If a file does not match these two numbers, then it is not a proper Wad
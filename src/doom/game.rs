@@ -0,0 +1,106 @@
+// game.rs
+
+/// Wads made for Heretic, Hexen and Strife share the Doom file format but
+/// give different meanings to the same linedef specials (most visibly which
+/// key opens which door). The Game enum lets the rest of the program ask
+/// which game a Wad was built for.
+
+use doom::lump::Lump;
+use doom::level::Level;
+
+
+// thing types only Hexen uses: the ettin, centaur, slaughtaur, afrit,
+// wendigo, stalker and heresiarch, the two manas and combined mana, and
+// the fighter's axe
+const HEXEN_THINGS: &[u16] = &[10030, 107, 115, 10060, 8020, 121, 10080, 122, 124, 8004, 8010];
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Game {
+    Doom, Heretic, Hexen, Strife,
+}
+
+
+impl Game {
+    /// Guess the game from the lumps in a Wad and the things in its levels
+    /// Heretic ships its music as MUS_xxxx lumps, Strife keeps dialogue in
+    /// SCRIPTxx lumps and Hexen has its cluster messages and startup screen,
+    /// while PWADs are told apart by monsters and items only one game has.
+    /// A BEHAVIOR lump only says a map is in Hexen format, which ports use
+    /// for every game, so it isn't taken as a sign of Hexen.
+    /// Anything else is assumed to be Doom.
+    pub fn detect(lumps: &[Lump], levels: &[Level]) -> Game {
+        for lump in lumps {
+            let name = lump.name.as_str();
+            if name.starts_with("MUS_") {
+                return Game::Heretic;
+            }
+            if name.starts_with("SCRIPT") || name == "ENDSTRF" {
+                return Game::Strife;
+            }
+            if name == "STARTUP" || name == "WIN1MSG" || (name.starts_with("CLUS") && name.ends_with("MSG")) {
+                return Game::Hexen;
+            }
+        }
+
+        let hexen_things = levels
+            .iter()
+            .flat_map(|l| l.things.iter())
+            .any(|t| HEXEN_THINGS.contains(&t.ttype));
+        match hexen_things {
+            true => Game::Hexen,
+            _    => Game::Doom,
+        }
+    }
+
+    // parse a game name given on the command line
+    pub fn from_name(name: &str) -> Option<Game> {
+        match name.to_lowercase().as_str() {
+            "doom"    => Some(Game::Doom),
+            "heretic" => Some(Game::Heretic),
+            "hexen"   => Some(Game::Hexen),
+            "strife"  => Some(Game::Strife),
+            _         => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Game::Doom    => "Doom",
+            Game::Heretic => "Heretic",
+            Game::Hexen   => "Hexen",
+            Game::Strife  => "Strife",
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use doom::game::*;
+    use doom::thing::Thing;
+
+    fn level_with(ttype: u16) -> Level {
+        Level{
+            name:     String::from("MAP01"),
+            things:   vec![Thing{ tid: 0, x: 0, y: 0, z: 0, angle: 0, ttype: ttype, flags: 0, special: 0, args: [0; 5] }],
+            vertices: Vec::new(),
+            linedefs: Vec::new(),
+            sidedefs: Vec::new(),
+            sectors:  Vec::new(),
+            hash:     0,
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let behavior = Lump::new(&[0, 0, 0, 0, 0, 0, 0, 0, b'B', b'E', b'H', b'A', b'V', b'I', b'O', b'R']);
+        let lumps = vec![behavior];
+
+        // a Hexen-format map full of zombiemen is still a Doom map
+        assert_eq!(Game::detect(&lumps, &[level_with(3004)]), Game::Doom);
+        assert_eq!(Game::detect(&lumps, &[level_with(10030)]), Game::Hexen);
+    }
+}
+
+// end
@@ -0,0 +1,212 @@
+// generalized.rs
+
+/// Boom-compatible ports pack the behavior of a linedef into the special
+/// number itself for any special in the range 0x2F80..0x7FFF. The low bits
+/// hold the trigger and speed, the high bits pick the action, and the bits
+/// in between are action-specific (lock type for locked doors and so on).
+/// This module unpacks those numbers so they can be drawn like any other
+/// special line.
+
+use doom::specials::Key;
+
+
+/// Base numbers for every generalized action, in ascending order
+pub const GEN_CRUSHER_BASE : u16 = 0x2F80;
+pub const GEN_STAIRS_BASE  : u16 = 0x3000;
+pub const GEN_LIFT_BASE    : u16 = 0x3400;
+pub const GEN_LOCKED_BASE  : u16 = 0x3800;
+pub const GEN_DOOR_BASE    : u16 = 0x3C00;
+pub const GEN_CEILING_BASE : u16 = 0x4000;
+pub const GEN_FLOOR_BASE   : u16 = 0x6000;
+pub const GEN_END          : u16 = 0x7FFF;
+
+
+/// How the line is activated, and whether it can be activated again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    WalkOnce, WalkRepeat,
+    SwitchOnce, SwitchRepeat,
+    GunOnce, GunRepeat,
+    DoorOnce, DoorRepeat,
+}
+
+
+/// How fast the moving sector travels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    Slow, Normal, Fast, Turbo,
+}
+
+
+/// The key (or keys) a generalized locked door asks for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lock {
+    AnyKey,
+    RedCard, BlueCard, YellowCard,
+    RedSkull, BlueSkull, YellowSkull,
+    AllKeys,
+}
+
+
+/// What the generalized special does once triggered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Floor,
+    Ceiling,
+    Door,
+    LockedDoor(Lock),
+    Lift,
+    Stairs,
+    Crusher,
+}
+
+
+/// A fully decoded generalized special
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Generalized {
+    pub trigger: Trigger,
+    pub speed:   Speed,
+    pub action:  Action,
+}
+
+
+impl Trigger {
+    // return if the trigger is pressed rather than walked over or shot
+    pub fn is_use(&self) -> bool {
+        matches!(
+            *self,
+            Trigger::SwitchOnce | Trigger::SwitchRepeat |
+            Trigger::DoorOnce   | Trigger::DoorRepeat
+        )
+    }
+}
+
+
+impl Lock {
+    // the key color a lock asks for, with skulls and cards sharing colors
+    pub fn key(&self) -> Key {
+        match *self {
+            Lock::AnyKey      => Key::Any,
+            Lock::AllKeys     => Key::All,
+            Lock::RedCard     => Key::Red,
+            Lock::RedSkull    => Key::Red,
+            Lock::BlueCard    => Key::Blue,
+            Lock::BlueSkull   => Key::Blue,
+            Lock::YellowCard  => Key::Yellow,
+            Lock::YellowSkull => Key::Yellow,
+        }
+    }
+}
+
+
+/// Return if a special number lies in the generalized range
+pub fn is_generalized(special: u16) -> bool {
+    (GEN_CRUSHER_BASE..=GEN_END).contains(&special)
+}
+
+
+/// Decode a generalized special into its parts
+/// Returns None if the number is not a generalized special
+pub fn decode(special: u16) -> Option<Generalized> {
+    if !is_generalized(special) {
+        return None;
+    }
+
+    let trigger = match special & 0x0007 {
+        0 => Trigger::WalkOnce,
+        1 => Trigger::WalkRepeat,
+        2 => Trigger::SwitchOnce,
+        3 => Trigger::SwitchRepeat,
+        4 => Trigger::GunOnce,
+        5 => Trigger::GunRepeat,
+        6 => Trigger::DoorOnce,
+        _ => Trigger::DoorRepeat,
+    };
+
+    let speed = match (special & 0x0018) >> 3 {
+        0 => Speed::Slow,
+        1 => Speed::Normal,
+        2 => Speed::Fast,
+        _ => Speed::Turbo,
+    };
+
+    let action = if special >= GEN_FLOOR_BASE {
+        Action::Floor
+    } else if special >= GEN_CEILING_BASE {
+        Action::Ceiling
+    } else if special >= GEN_DOOR_BASE {
+        Action::Door
+    } else if special >= GEN_LOCKED_BASE {
+        Action::LockedDoor(decode_lock(special))
+    } else if special >= GEN_LIFT_BASE {
+        Action::Lift
+    } else if special >= GEN_STAIRS_BASE {
+        Action::Stairs
+    } else {
+        Action::Crusher
+    };
+
+    Some(Generalized{
+        trigger: trigger,
+        speed:   speed,
+        action:  action,
+    })
+}
+
+
+// locked doors keep the key type in bits 6-8 (bit 9 only says whether
+// skulls and keycards of one color are interchangeable, which doesn't
+// change the color of the door)
+fn decode_lock(special: u16) -> Lock {
+    match (special & 0x01C0) >> 6 {
+        0 => Lock::AnyKey,
+        1 => Lock::RedCard,
+        2 => Lock::BlueCard,
+        3 => Lock::YellowCard,
+        4 => Lock::RedSkull,
+        5 => Lock::BlueSkull,
+        6 => Lock::YellowSkull,
+        _ => Lock::AllKeys,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use doom::generalized::*;
+
+    #[test]
+    fn test_not_generalized() {
+        assert_eq!(decode(0), None);
+        assert_eq!(decode(28), None);
+        assert_eq!(decode(0x2F7F), None);
+        assert_eq!(decode(0x8000), None);
+    }
+
+    #[test]
+    fn test_locked_door() {
+        // DR, normal speed, red keycard
+        let g = decode(GEN_LOCKED_BASE | 0x0007 | 0x0008 | (1 << 6)).unwrap();
+        assert_eq!(g.trigger, Trigger::DoorRepeat);
+        assert_eq!(g.speed, Speed::Normal);
+        assert_eq!(g.action, Action::LockedDoor(Lock::RedCard));
+
+        // S1, turbo, yellow skull
+        let g = decode(GEN_LOCKED_BASE | 0x0002 | 0x0018 | (6 << 6)).unwrap();
+        assert_eq!(g.trigger, Trigger::SwitchOnce);
+        assert_eq!(g.speed, Speed::Turbo);
+        assert_eq!(g.action, Action::LockedDoor(Lock::YellowSkull));
+    }
+
+    #[test]
+    fn test_action_ranges() {
+        assert_eq!(decode(GEN_CRUSHER_BASE).unwrap().action, Action::Crusher);
+        assert_eq!(decode(GEN_STAIRS_BASE).unwrap().action, Action::Stairs);
+        assert_eq!(decode(GEN_LIFT_BASE).unwrap().action, Action::Lift);
+        assert_eq!(decode(GEN_DOOR_BASE).unwrap().action, Action::Door);
+        assert_eq!(decode(GEN_CEILING_BASE).unwrap().action, Action::Ceiling);
+        assert_eq!(decode(GEN_END).unwrap().action, Action::Floor);
+    }
+}
+
+// end
@@ -1,26 +1,50 @@
 // level.rs
 
+use std::cmp::{min, max};
+use std::collections::HashMap;
 use utils::packet_range;
+use hash::Hasher;
 use doom::linedef::*;
 use doom::vertex::*;
+use doom::sector::*;
+use doom::sidedef::*;
+use doom::thing::*;
 use doom::constants::{DOOM_LINEDEF_WIDTH, HEXEN_LINEDEF_WIDTH, VERTEX_WIDTH};
+use doom::constants::{SECTOR_WIDTH, SIDEDEF_WIDTH, DOOM_THING_WIDTH, HEXEN_THING_WIDTH};
+
+
+/// The raw data of every lump a Level is built from, sliced out of the Wad
+/// A lump missing from the Wad is given as an empty slice
+pub struct LevelLumps<'a> {
+    pub things:   &'a [u8],
+    pub vertexes: &'a [u8],
+    pub linedefs: &'a [u8],
+    pub sidedefs: &'a [u8],
+    pub sectors:  &'a [u8],
+}
 
 
 /// A Level is a collection of all types of Lump group categories into one piece.
-/// A Level here has two lists, a VERTEXES and LINEDEFS list.
-/// TODO: add support for THINGS
+/// A Level here has lists of THINGS, VERTEXES, LINEDEFS, SIDEDEFS and SECTORS.
 pub struct Level {
     pub name:     String,
+    pub things:   Vec<Thing>,
     pub vertices: Vec<Vertex>,
     pub linedefs: Vec<LineDef>,
+    pub sidedefs: Vec<SideDef>,
+    pub sectors:  Vec<Sector>,
+    pub hash:     u64, // of the lumps the level was read from
 }
 
 
 impl Level {
-    pub fn new(name: &String, vert_raw: &[u8], ld_raw: &[u8], is_hexen: bool) -> Level {
+    pub fn new(name: &String, raw: &LevelLumps, is_hexen: bool) -> Level {
         // create vectors of items we need to store
+        let mut things   : Vec<Thing> = Vec::new();
         let mut vertices : Vec<Vertex> = Vec::new();
         let mut linedefs : Vec<LineDef> = Vec::new();
+        let mut sidedefs : Vec<SideDef> = Vec::new();
+        let mut sectors  : Vec<Sector> = Vec::new();
 
         // determine the width we will be using for LINEDEF scanning
         let ld_width : usize = match is_hexen {
@@ -28,32 +52,251 @@ impl Level {
             false => DOOM_LINEDEF_WIDTH,
         };
 
+        // THINGS grow from 10 to 20 bytes in Hexen just like LINEDEFS do
+        let th_width : usize = match is_hexen {
+            true => HEXEN_THING_WIDTH,
+            false => DOOM_THING_WIDTH,
+        };
+
         let mut offset : usize = 0;
-        while offset < vert_raw.len() {
-            vertices.push(Vertex::new(&vert_raw[packet_range(offset, VERTEX_WIDTH)]));
+        while offset + th_width <= raw.things.len() {
+            things.push(Thing::new(
+                is_hexen,
+                &raw.things[packet_range(offset, th_width)],
+            ));
+            offset += th_width;
+        }
+
+        offset = 0;
+        while offset + VERTEX_WIDTH <= raw.vertexes.len() {
+            vertices.push(Vertex::new(&raw.vertexes[packet_range(offset, VERTEX_WIDTH)]));
             offset += VERTEX_WIDTH;
         }
 
         offset = 0;
-        while offset < ld_raw.len() {
+        while offset + ld_width <= raw.linedefs.len() {
             linedefs.push(LineDef::new(
                 is_hexen,
-                &ld_raw[packet_range(offset, ld_width)],
+                &raw.linedefs[packet_range(offset, ld_width)],
             ));
             offset += ld_width;
         }
 
+        offset = 0;
+        while offset + SIDEDEF_WIDTH <= raw.sidedefs.len() {
+            sidedefs.push(SideDef::new(&raw.sidedefs[packet_range(offset, SIDEDEF_WIDTH)]));
+            offset += SIDEDEF_WIDTH;
+        }
+
+        offset = 0;
+        while offset + SECTOR_WIDTH <= raw.sectors.len() {
+            sectors.push(Sector::new(&raw.sectors[packet_range(offset, SECTOR_WIDTH)]));
+            offset += SECTOR_WIDTH;
+        }
+
+        let mut hasher = Hasher::new();
+        for lump in &[raw.things, raw.vertexes, raw.linedefs, raw.sidedefs, raw.sectors] {
+            hasher.piece(lump);
+        }
+
         Level{
             name:     name.to_owned(),
+            things:   things,
             vertices: vertices,
             linedefs: linedefs,
+            sidedefs: sidedefs,
+            sectors:  sectors,
+            hash:     hasher.finish(),
+        }
+    }
+
+    // return the sector a sidedef faces, if the side exists
+    // linedefs mark a missing side with -1
+    pub fn side_sector(&self, side: i16) -> Option<&Sector> {
+        if side < 0 {
+            return None;
         }
+        self.sidedefs
+            .get(side as usize)
+            .and_then(|sd| self.sectors.get(sd.sector as usize))
+    }
+
+    // the bounding box (min_x, min_y, max_x, max_y) of every vertex used by
+    // a linedef; node builders leave seg-split and unused vertices behind
+    // that would otherwise stretch the map with empty space
+    pub fn bounds(&self) -> Option<(i16, i16, i16, i16)> {
+        bounding_box(
+            self.linedefs
+                .iter()
+                .flat_map(|l| vec![l.start, l.end])
+                .filter_map(|v| self.vertices.get(v))
+                .map(|v| (v.x, v.y)),
+        )
+    }
+
+    // the bounding box of every thing placed in the level
+    pub fn thing_bounds(&self) -> Option<(i16, i16, i16, i16)> {
+        bounding_box(self.things.iter().map(|t| (t.x, t.y)))
+    }
+
+    // trace the outline of every sector as closed loops of vertex indices,
+    // returned in sector order. Every linedef side facing a sector is an
+    // edge (walked so the sector is always on the right), and edges are
+    // chained end-to-start until they come back around. Loops that never
+    // close are dropped, and lines with one sector on both sides cancel out.
+    // Holes come out as extra loops, so fill them with the even-odd rule.
+    pub fn sector_loops(&self) -> Vec<Vec<Vec<usize>>> {
+        let side_sector = |side: i16| -> Option<usize> {
+            if side < 0 {
+                return None;
+            }
+            self.sidedefs
+                .get(side as usize)
+                .map(|sd| sd.sector as usize)
+                .filter(|&sec| sec < self.sectors.len())
+        };
+
+        let mut edges: Vec<Vec<(usize, usize)>> = vec![Vec::new(); self.sectors.len()];
+        for line in &self.linedefs {
+            let front = side_sector(line.right);
+            let back = side_sector(line.left);
+            if front == back {
+                continue;
+            }
+            if let Some(sec) = front {
+                edges[sec].push((line.start, line.end));
+            }
+            if let Some(sec) = back {
+                edges[sec].push((line.end, line.start));
+            }
+        }
+
+        edges.iter().map(|sector_edges| chain_edges(sector_edges)).collect()
+    }
+
+    // describe every reference in the level that points at something
+    // missing, which is what trips up ports and editors on damaged maps
+    pub fn problems(&self) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+
+        for (i, line) in self.linedefs.iter().enumerate() {
+            for &v in &[line.start, line.end] {
+                if v >= self.vertices.len() {
+                    found.push(format!("linedef {} uses missing vertex {}", i, v));
+                }
+            }
+            if line.left < 0 && line.right < 0 {
+                found.push(format!("linedef {} has no sides", i));
+            }
+            for &side in &[line.right, line.left] {
+                if side >= 0 && side as usize >= self.sidedefs.len() {
+                    found.push(format!("linedef {} uses missing sidedef {}", i, side));
+                }
+            }
+        }
+
+        for (i, side) in self.sidedefs.iter().enumerate() {
+            if side.sector as usize >= self.sectors.len() {
+                found.push(format!("sidedef {} faces missing sector {}", i, side.sector));
+            }
+        }
+
+        // thing type 1 is the first player's start in every game
+        if !self.things.iter().any(|t| t.ttype == 1) {
+            found.push(String::from("no player 1 start"));
+        }
+        found
     }
 
     pub fn print(&self) {
-        println!("Level name: {}", self.name);
-        println!("Vertices: {}", self.vertices.len());
-        println!("Linedefs: {}", self.linedefs.len());
+        say!("Level name: {}", self.name);
+        say!("Things: {}", self.things.len());
+        say!("Vertices: {}", self.vertices.len());
+        say!("Linedefs: {}", self.linedefs.len());
+        say!("Sidedefs: {}", self.sidedefs.len());
+        say!("Sectors: {}", self.sectors.len());
+    }
+}
+
+
+// the smallest box holding every point, if there are any points
+fn bounding_box<I>(points: I) -> Option<(i16, i16, i16, i16)>
+    where I: Iterator<Item = (i16, i16)>
+{
+    points.fold(None, |bounds, (x, y)| match bounds {
+        None => Some((x, y, x, y)),
+        Some((x1, y1, x2, y2)) => Some((min(x1, x), min(y1, y), max(x2, x), max(y2, y))),
+    })
+}
+
+
+// chain a sector's edges into closed loops of vertex indices
+fn chain_edges(edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut starts: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(a, _)) in edges.iter().enumerate() {
+        starts.entry(a).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut loops: Vec<Vec<usize>> = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+
+        let (origin, mut at) = edges[first];
+        let mut verts = vec![origin];
+        while at != origin {
+            verts.push(at);
+            let next = starts
+                .get(&at)
+                .and_then(|list| list.iter().cloned().find(|&e| !used[e]));
+            match next {
+                Some(e) => { used[e] = true; at = edges[e].1; }
+                None    => { break; }
+            }
+        }
+
+        if at == origin && verts.len() >= 3 {
+            loops.push(verts);
+        }
+    }
+    loops
+}
+
+
+#[cfg(test)]
+mod tests {
+    use doom::level::*;
+
+    #[test]
+    fn test_bounding_box() {
+        // a vertex at the origin must not reset the box
+        let points = vec![(0, 0), (-5, 3), (10, -2), (0, 0)];
+        assert_eq!(bounding_box(points.into_iter()), Some((-5, -2, 10, 3)));
+        assert_eq!(bounding_box(Vec::new().into_iter()), None);
+    }
+
+    #[test]
+    fn test_problems() {
+        let lev = Level{
+            name:     String::from("MAP01"),
+            things:   Vec::new(),
+            vertices: vec![Vertex{ x: 0, y: 0 }, Vertex{ x: 64, y: 0 }],
+            linedefs: vec![LineDef{
+                start: 0, end: 2, left: -1, right: 0,
+                tag: 0, flags: 0, stype: 0, args: [0; 6],
+            }],
+            sidedefs: Vec::new(),
+            sectors:  Vec::new(),
+            hash:     0,
+        };
+        assert_eq!(lev.problems(), vec![
+            "linedef 0 uses missing vertex 2",
+            "linedef 0 uses missing sidedef 0",
+            "no player 1 start",
+        ]);
     }
 }
 
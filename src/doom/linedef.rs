@@ -2,6 +2,7 @@
 
 use utils::{u8_to_i16, u8_to_u16};
 use doom::constants::{DOOM_LINEDEF_WIDTH, HEXEN_LINEDEF_WIDTH};
+use doom::constants::{ML_SECRET, ML_DONTDRAW, ML_MAPPED};
 
 
 /// A LineDef is a representation of a Line on a Doom level. Map objects such as
@@ -58,7 +59,7 @@ impl LineDef {
     }
 
     pub fn print(&self) {
-        println!("Linedef flag: {}", self.stype);
+        say!("Linedef flag: {}", self.stype);
     }
 
     // return if a linedef is "one-sided", meaning space behind it is void
@@ -75,6 +76,27 @@ impl LineDef {
             x => x,
         }
     }
+
+    // secret lines show up as plain walls on the automap
+    pub fn is_secret(&self) -> bool {
+        self.flags & ML_SECRET != 0
+    }
+
+    // hidden lines never show up on the automap
+    pub fn is_hidden(&self) -> bool {
+        self.flags & ML_DONTDRAW != 0
+    }
+
+    // mapped lines are on the automap from the moment the level starts
+    pub fn is_mapped(&self) -> bool {
+        self.flags & ML_MAPPED != 0
+    }
+
+    // return if a Hexen linedef is activated by the player pressing use
+    // the activation type is stored in bits 10-12 of the flags
+    pub fn is_use_activated(&self) -> bool {
+        (self.flags & 0x1C00) >> 10 == 1
+    }
 }
 
 // end
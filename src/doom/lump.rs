@@ -54,7 +54,7 @@ impl Lump {
 
     // debugging purposes
     pub fn print(&self) {
-        println!("{} - 0x{:X}, size: {}", self.name, self.posn, self.size);
+        say!("{} - 0x{:X}, size: {}", self.name, self.posn, self.size);
     }
 
     // return the range that the lump lies in
@@ -0,0 +1,168 @@
+// mapinfo.rs
+
+/// Level titles don't live in the level lumps; ports read them from a
+/// MAPINFO-style lump instead. Three flavors are common:
+///
+///   Hexen MAPINFO:  map 1 "WINNOWING HALL"
+///   ZDoom MAPINFO:  map MAP01 "Entryway" { ... }
+///   UMAPINFO:       MAP MAP01 { levelname = "Entryway" }
+///
+/// This module pulls (level lump name, title) pairs out of any of them.
+/// Titles looked up from a LANGUAGE lump (`map MAP01 lookup HUSTR_1`)
+/// are skipped, since we don't read LANGUAGE.
+
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(char),
+}
+
+
+// split MAPINFO text into words, quoted strings and single symbols
+// comments start with ';' or '//' and run to the end of the line
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == ',' {
+            chars.next();
+        } else if c == ';' || (c == '/' && text_follows(&chars, "//")) {
+            while let Some(&c) = chars.peek() {
+                if c == '\n' { break; }
+                chars.next();
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"'  => { break; }
+                    '\\' => { if let Some(n) = chars.next() { s.push(n); } }
+                    _    => { s.push(c); }
+                }
+            }
+            tokens.push(Token::Quoted(s));
+        } else if c == '{' || c == '}' || c == '=' {
+            chars.next();
+            tokens.push(Token::Symbol(c));
+        } else {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || "{}=\",;".contains(c) { break; }
+                s.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(s));
+        }
+    }
+    tokens
+}
+
+
+// peek whether the upcoming characters spell out a given string
+fn text_follows<I: Iterator<Item = char> + Clone>(chars: &I, s: &str) -> bool {
+    chars.clone().take(s.len()).eq(s.chars())
+}
+
+
+// Hexen numbers its maps, so "map 1" means MAP01
+fn map_lump_name(name: &str) -> String {
+    match name.parse::<u32>() {
+        Ok(n)  => format!("MAP{:02}", n),
+        Err(_) => name.to_uppercase(),
+    }
+}
+
+
+/// Return every (level lump name, title) pair found in the text
+pub fn parse_titles(text: &str) -> Vec<(String, String)> {
+    let tokens = tokenize(text);
+    let mut titles: Vec<(String, String)> = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_map = match tokens[i] {
+            Token::Word(ref w) => w.eq_ignore_ascii_case("map"),
+            _                  => false,
+        };
+        if !is_map {
+            i += 1;
+            continue;
+        }
+
+        let name = match tokens.get(i + 1) {
+            Some(Token::Word(w)) => map_lump_name(w),
+            _ => { i += 1; continue; }
+        };
+        i += 2;
+
+        match tokens.get(i) {
+            // Hexen and ZDoom put the title right after the name
+            Some(Token::Quoted(title)) => {
+                titles.push((name, title.to_owned()));
+            }
+
+            // UMAPINFO keeps it in a levelname field inside the block
+            Some(Token::Symbol('{')) => {
+                let mut depth = 0;
+                while i < tokens.len() {
+                    match tokens[i] {
+                        Token::Symbol('{') => { depth += 1; }
+                        Token::Symbol('}') => {
+                            depth -= 1;
+                            if depth == 0 { break; }
+                        }
+                        Token::Word(ref w) if w.eq_ignore_ascii_case("levelname") => {
+                            if let (Some(Token::Symbol('=')), Some(Token::Quoted(title))) =
+                                (tokens.get(i + 1), tokens.get(i + 2))
+                            {
+                                titles.push((name.to_owned(), title.to_owned()));
+                            }
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+
+            _ => {}
+        }
+    }
+    titles
+}
+
+
+#[cfg(test)]
+mod tests {
+    use doom::mapinfo::*;
+
+    #[test]
+    fn test_hexen_mapinfo() {
+        let text = "; Hexen\nmap 1 \"WINNOWING HALL\"\nwarptrans 1\nnext 2\n\nmap 2 \"SEVEN PORTALS\"\n";
+        assert_eq!(parse_titles(text), vec![
+            (String::from("MAP01"), String::from("WINNOWING HALL")),
+            (String::from("MAP02"), String::from("SEVEN PORTALS")),
+        ]);
+    }
+
+    #[test]
+    fn test_zdoom_mapinfo() {
+        let text = "map E1M1 \"Hangar\" // first map\n{\n  next = \"E1M2\"\n}\nmap MAP01 lookup HUSTR_1\n";
+        assert_eq!(parse_titles(text), vec![
+            (String::from("E1M1"), String::from("Hangar")),
+        ]);
+    }
+
+    #[test]
+    fn test_umapinfo() {
+        let text = "MAP MAP07\n{\n  levelname = \"Dead Simple\"\n  next = \"MAP08\"\n}\n";
+        assert_eq!(parse_titles(text), vec![
+            (String::from("MAP07"), String::from("Dead Simple")),
+        ]);
+    }
+}
+
+// end
@@ -6,7 +6,13 @@
 pub mod constants;
 pub mod vertex;
 pub mod linedef;
+pub mod specials;
+pub mod generalized;
+pub mod game;
 pub mod sector;
+pub mod sidedef;
+pub mod thing;
+pub mod mapinfo;
 pub mod lump;
 pub mod level;
 pub mod wad;
@@ -9,10 +9,30 @@ use utils::*;
 use doom::constants::SECTOR_WIDTH;
 
 
+/// The kinds of liquid floor a sector can have, told apart by flat name
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Liquid {
+    Nukage, Water, Lava, Blood, Slime,
+}
+
+
+/// Flat name prefixes for every liquid, Doom's first and then Heretic's
+const LIQUID_FLATS: &[(&str, Liquid)] = &[
+    ("NUKAGE",  Liquid::Nukage),
+    ("FWATER",  Liquid::Water),
+    ("LAVA",    Liquid::Lava),
+    ("BLOOD",   Liquid::Blood),
+    ("SLIME",   Liquid::Slime),
+    ("FLTWAWA", Liquid::Water),
+    ("FLTLAVA", Liquid::Lava),
+    ("FLTSLUD", Liquid::Slime),
+];
+
+
 pub struct Sector {
-    pub ceil:      u16,
+    pub ceil:      i16,
     pub stag:      u16,
-    pub floor:     u16,
+    pub floor:     i16,
     pub light:     u16,
     pub stype:     u16,
     pub ceil_tex:  String,
@@ -23,44 +43,70 @@ pub struct Sector {
 impl Sector {
     pub fn new(dat: &[u8]) -> Sector {
         if dat.len() != SECTOR_WIDTH {
-            panic!(format!(
+            panic!(
                 "Sector given {} bytes, needs {}",
                 dat.len(),
                 SECTOR_WIDTH
-            ));
+            );
         }
 
-        // calculate the end of the strings so no NUL bytes are included
-        let mut zero1: usize = 11;
-        let mut zero2: usize = 19;
-        while dat[zero1] == 0 { zero1 -= 1; }
-        while dat[zero2] == 0 { zero2 -= 1; }
-
-        println!("Making sector, len: {}", dat.len());
-        println!(
-            "Floor: {}",
-            String::from_utf8_lossy(&dat[4..(zero1 + 1)]).to_string()
-        );
-        println!(
-            "Ceil: {}",
-            String::from_utf8_lossy(&dat[12..(zero2 + 1)]).to_string()
-        );
         Sector{
-            floor:     u8_to_u16(dat[0],   dat[1]),
-            ceil:      u8_to_u16(dat[2],   dat[3]),
+            floor:     u8_to_i16(dat[0],   dat[1]),
+            ceil:      u8_to_i16(dat[2],   dat[3]),
             light:     u8_to_u16(dat[20], dat[21]),
-            stype:     u8_to_u16(dat[2],   dat[3]),
-            stag:      u8_to_u16(dat[2],   dat[3]),
-            floor_tex: String::from_utf8_lossy(&dat[4..(zero1+1)]).to_string(),
-            ceil_tex:  String::from_utf8_lossy(&dat[12..(zero2+1)]).to_string(),
+            stype:     u8_to_u16(dat[22], dat[23]),
+            stag:      u8_to_u16(dat[24], dat[25]),
+            floor_tex: u8_to_str(&dat[4..12]),
+            ceil_tex:  u8_to_str(&dat[12..20]),
         }
     }
 
+    // return if entering the sector counts towards the secret total
+    // Doom uses special 9, Boom adds a secret bit (0x80) on top of the
+    // lighting special, and ZDoom's Hexen-format maps use bit 0x400
+    pub fn is_secret(&self, is_hexen: bool) -> bool {
+        match is_hexen {
+            true => self.stype & 0x0400 != 0,
+            _    => self.stype & 0x001F == 9 || self.stype & 0x0080 != 0,
+        }
+    }
+
+    // return how much damage the floor does to the player, or 0 if it's safe
+    // Doom's damaging specials are 5 (10%), 7 (5%) and 4, 11 and 16 (20%),
+    // Boom adds a damage field in bits 5-6 on top of the lighting special
+    pub fn damage(&self, is_hexen: bool) -> u16 {
+        if is_hexen {
+            return 0;
+        }
+
+        match (self.stype & 0x0060) >> 5 {
+            1 => return 5,
+            2 => return 10,
+            3 => return 20,
+            _ => {}
+        }
+
+        match self.stype & 0x001F {
+            7           => 5,
+            5           => 10,
+            4 | 11 | 16 => 20,
+            _           => 0,
+        }
+    }
+
+    // return the kind of liquid on the floor, going by the flat's name
+    pub fn liquid(&self) -> Option<Liquid> {
+        LIQUID_FLATS
+            .iter()
+            .find(|&&(prefix, _)| self.floor_tex.starts_with(prefix))
+            .map(|&(_, liquid)| liquid)
+    }
+
     pub fn print(&self) {
-        println!("Sector tag {}:", self.stag);
-        println!("Floor texture: {}", self.floor_tex);
-        println!("Ceiling texture: {}", self.ceil_tex);
-        println!("Heights: F({}), C({})", self.floor, self.ceil);
+        say!("Sector tag {}:", self.stag);
+        say!("Floor texture: {}", self.floor_tex);
+        say!("Ceiling texture: {}", self.ceil_tex);
+        say!("Heights: F({}), C({})", self.floor, self.ceil);
     }
 }
 
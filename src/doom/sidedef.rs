@@ -1,7 +1,7 @@
 // sidedef.rs
 
 
-use utils::{u8_to_u16, u8_to_i16};
+use utils::{u8_to_u16, u8_to_i16, u8_to_str};
 use doom::constants::SIDEDEF_WIDTH;
 
 
@@ -25,14 +25,15 @@ impl SideDef {
             panic!("SideDef given {} bytes, needs {}", dat.len(), SIDEDEF_WIDTH);
         }
 
-        // calculate string lengths so no NUL bytes are included
-        let mut zero1 : usize = 11;
-        let mut zero2 : usize = 19;
-        while dat[zero1] == 0 { zero1 -= 1; }
-        while dat[zero2] == 0 { zero2 -= 1; }
-
         SideDef{
-            
+            x_offset:   u8_to_i16(dat[0],   dat[1]),
+            y_offset:   u8_to_i16(dat[2],   dat[3]),
+            upper_tex:  u8_to_str(&dat[4..12]),
+            lower_tex:  u8_to_str(&dat[12..20]),
+            middle_tex: u8_to_str(&dat[20..28]),
+            sector:     u8_to_u16(dat[28], dat[29]),
         }
     }
 }
+
+// end
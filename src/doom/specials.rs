@@ -0,0 +1,588 @@
+// specials.rs
+
+/// Linedef specials are plain numbers whose meaning depends on the game
+/// a Wad was built for. This module holds per-game tables that sort each
+/// special into a broad class (door, lift, exit, ...) so the mapmaker can
+/// color lines by what they do instead of by their raw number.
+
+use doom::game::Game;
+use doom::linedef::LineDef;
+use doom::generalized::{self, Action};
+
+
+/// The keys a locked door can ask for, across every supported game
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    // Doom and Heretic
+    Red, Blue, Yellow, Green, Any, All,
+
+    // Hexen
+    Steel, Cave, Axe, Fire, Emerald, Dungeon,
+    Silver, Rusted, Horn, Swamp, Castle,
+
+    // Strife, which has a silver key of its own too
+    IdCard, PassCard, IdBadge, Brass, Gold, Base, Governor,
+    Order, Chalice, Core, Chapel, Catacomb, OraclePass,
+
+    // a lock the tables don't know
+    Unknown,
+}
+
+
+/// The broad category a linedef special falls into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineClass {
+    Normal,
+    Door,
+    LockedDoor(Key),
+    Lift,
+    Exit,
+    SecretExit,
+    Teleporter,
+    Switch,
+    Crusher,
+    Stairs,
+}
+
+
+impl LineClass {
+    // a short lowercase name for the class, for exported data
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LineClass::Normal        => "normal",
+            LineClass::Door          => "door",
+            LineClass::LockedDoor(_) => "locked-door",
+            LineClass::Lift          => "lift",
+            LineClass::Exit          => "exit",
+            LineClass::SecretExit    => "secret-exit",
+            LineClass::Teleporter    => "teleporter",
+            LineClass::Switch        => "switch",
+            LineClass::Crusher       => "crusher",
+            LineClass::Stairs        => "stairs",
+        }
+    }
+}
+
+
+impl Key {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Key::Red      => "Red key",
+            Key::Blue     => "Blue key",
+            Key::Yellow   => "Yellow key",
+            Key::Green    => "Green key",
+            Key::Any      => "Any key",
+            Key::All      => "All keys",
+            Key::Steel    => "Steel key",
+            Key::Cave     => "Cave key",
+            Key::Axe      => "Axe key",
+            Key::Fire     => "Fire key",
+            Key::Emerald  => "Emerald key",
+            Key::Dungeon  => "Dungeon key",
+            Key::Silver   => "Silver key",
+            Key::Rusted   => "Rusted key",
+            Key::Horn     => "Horn key",
+            Key::Swamp    => "Swamp key",
+            Key::Castle   => "Castle key",
+            Key::IdCard   => "ID card",
+            Key::PassCard => "Pass card",
+            Key::IdBadge  => "ID badge",
+            Key::Brass    => "Brass key",
+            Key::Gold     => "Gold key",
+            Key::Base     => "Base key",
+            Key::Governor => "Governor's key",
+            Key::Order    => "Order key",
+            Key::Chalice  => "Chalice",
+            Key::Core     => "Core key",
+            Key::Chapel   => "Chapel key",
+            Key::Catacomb => "Catacomb key",
+            Key::OraclePass => "Oracle pass",
+            Key::Unknown  => "Unknown key",
+        }
+    }
+}
+
+
+/// Doom (and Boom extended) specials, sorted by special number
+const DOOM_SPECIALS: &[(u16, LineClass)] = &[
+    (1,   LineClass::Door),
+    (2,   LineClass::Door),
+    (3,   LineClass::Door),
+    (4,   LineClass::Door),
+    (6,   LineClass::Crusher),
+    (7,   LineClass::Stairs),
+    (8,   LineClass::Stairs),
+    (9,   LineClass::Switch),
+    (10,  LineClass::Lift),
+    (11,  LineClass::Exit),
+    (14,  LineClass::Switch),
+    (15,  LineClass::Switch),
+    (16,  LineClass::Door),
+    (18,  LineClass::Switch),
+    (20,  LineClass::Switch),
+    (21,  LineClass::Lift),
+    (23,  LineClass::Switch),
+    (25,  LineClass::Crusher),
+    (26,  LineClass::LockedDoor(Key::Blue)),
+    (27,  LineClass::LockedDoor(Key::Yellow)),
+    (28,  LineClass::LockedDoor(Key::Red)),
+    (29,  LineClass::Door),
+    (31,  LineClass::Door),
+    (32,  LineClass::LockedDoor(Key::Blue)),
+    (33,  LineClass::LockedDoor(Key::Red)),
+    (34,  LineClass::LockedDoor(Key::Yellow)),
+    (39,  LineClass::Teleporter),
+    (41,  LineClass::Switch),
+    (42,  LineClass::Door),
+    (43,  LineClass::Switch),
+    (45,  LineClass::Switch),
+    (46,  LineClass::Door),
+    (49,  LineClass::Crusher),
+    (50,  LineClass::Door),
+    (51,  LineClass::SecretExit),
+    (52,  LineClass::Exit),
+    (55,  LineClass::Switch),
+    (57,  LineClass::Crusher),
+    (60,  LineClass::Switch),
+    (61,  LineClass::Door),
+    (62,  LineClass::Lift),
+    (63,  LineClass::Door),
+    (64,  LineClass::Switch),
+    (65,  LineClass::Switch),
+    (66,  LineClass::Switch),
+    (67,  LineClass::Switch),
+    (68,  LineClass::Switch),
+    (69,  LineClass::Switch),
+    (70,  LineClass::Switch),
+    (71,  LineClass::Switch),
+    (73,  LineClass::Crusher),
+    (74,  LineClass::Crusher),
+    (75,  LineClass::Door),
+    (76,  LineClass::Door),
+    (77,  LineClass::Crusher),
+    (86,  LineClass::Door),
+    (88,  LineClass::Lift),
+    (90,  LineClass::Door),
+    (97,  LineClass::Teleporter),
+    (99,  LineClass::LockedDoor(Key::Blue)),
+    (100, LineClass::Stairs),
+    (101, LineClass::Switch),
+    (102, LineClass::Switch),
+    (103, LineClass::Door),
+    (105, LineClass::Door),
+    (106, LineClass::Door),
+    (107, LineClass::Door),
+    (108, LineClass::Door),
+    (109, LineClass::Door),
+    (110, LineClass::Door),
+    (111, LineClass::Door),
+    (112, LineClass::Door),
+    (113, LineClass::Door),
+    (114, LineClass::Door),
+    (115, LineClass::Door),
+    (116, LineClass::Door),
+    (117, LineClass::Door),
+    (118, LineClass::Door),
+    (120, LineClass::Lift),
+    (121, LineClass::Lift),
+    (122, LineClass::Lift),
+    (123, LineClass::Lift),
+    (124, LineClass::SecretExit),
+    (125, LineClass::Teleporter),
+    (126, LineClass::Teleporter),
+    (127, LineClass::Stairs),
+    (131, LineClass::Switch),
+    (132, LineClass::Switch),
+    (133, LineClass::LockedDoor(Key::Blue)),
+    (134, LineClass::LockedDoor(Key::Red)),
+    (135, LineClass::LockedDoor(Key::Red)),
+    (136, LineClass::LockedDoor(Key::Yellow)),
+    (137, LineClass::LockedDoor(Key::Yellow)),
+    (138, LineClass::Switch),
+    (139, LineClass::Switch),
+    (140, LineClass::Switch),
+    (141, LineClass::Crusher),
+    (174, LineClass::Teleporter),
+    (195, LineClass::Teleporter),
+    (197, LineClass::Exit),
+    (198, LineClass::SecretExit),
+    (207, LineClass::Teleporter),
+    (208, LineClass::Teleporter),
+    (209, LineClass::Teleporter),
+    (210, LineClass::Teleporter),
+    (243, LineClass::Teleporter),
+    (244, LineClass::Teleporter),
+    (256, LineClass::Stairs),
+    (257, LineClass::Stairs),
+    (258, LineClass::Stairs),
+    (259, LineClass::Stairs),
+    (262, LineClass::Teleporter),
+    (263, LineClass::Teleporter),
+    (264, LineClass::Teleporter),
+    (265, LineClass::Teleporter),
+    (266, LineClass::Teleporter),
+    (267, LineClass::Teleporter),
+    (268, LineClass::Teleporter),
+    (269, LineClass::Teleporter),
+];
+
+
+/// Heretic specials, sorted by special number
+/// Heretic keeps most of Doom's numbers below 100 but swaps the red key for
+/// a green one, and adds a few specials of its own above that
+const HERETIC_SPECIALS: &[(u16, LineClass)] = &[
+    (1,   LineClass::Door),
+    (2,   LineClass::Door),
+    (3,   LineClass::Door),
+    (4,   LineClass::Door),
+    (6,   LineClass::Crusher),
+    (7,   LineClass::Stairs),
+    (8,   LineClass::Stairs),
+    (9,   LineClass::Switch),
+    (10,  LineClass::Lift),
+    (11,  LineClass::Exit),
+    (14,  LineClass::Switch),
+    (15,  LineClass::Switch),
+    (16,  LineClass::Door),
+    (18,  LineClass::Switch),
+    (20,  LineClass::Switch),
+    (21,  LineClass::Lift),
+    (23,  LineClass::Switch),
+    (25,  LineClass::Crusher),
+    (26,  LineClass::LockedDoor(Key::Blue)),
+    (27,  LineClass::LockedDoor(Key::Yellow)),
+    (28,  LineClass::LockedDoor(Key::Green)),
+    (29,  LineClass::Door),
+    (31,  LineClass::Door),
+    (32,  LineClass::LockedDoor(Key::Blue)),
+    (33,  LineClass::LockedDoor(Key::Green)),
+    (34,  LineClass::LockedDoor(Key::Yellow)),
+    (39,  LineClass::Teleporter),
+    (41,  LineClass::Switch),
+    (42,  LineClass::Door),
+    (43,  LineClass::Switch),
+    (45,  LineClass::Switch),
+    (46,  LineClass::Door),
+    (49,  LineClass::Crusher),
+    (50,  LineClass::Door),
+    (51,  LineClass::SecretExit),
+    (52,  LineClass::Exit),
+    (55,  LineClass::Switch),
+    (57,  LineClass::Crusher),
+    (60,  LineClass::Switch),
+    (61,  LineClass::Door),
+    (62,  LineClass::Lift),
+    (63,  LineClass::Door),
+    (64,  LineClass::Switch),
+    (65,  LineClass::Switch),
+    (66,  LineClass::Switch),
+    (67,  LineClass::Switch),
+    (68,  LineClass::Switch),
+    (69,  LineClass::Switch),
+    (70,  LineClass::Switch),
+    (71,  LineClass::Switch),
+    (73,  LineClass::Crusher),
+    (74,  LineClass::Crusher),
+    (75,  LineClass::Door),
+    (76,  LineClass::Door),
+    (77,  LineClass::Crusher),
+    (86,  LineClass::Door),
+    (88,  LineClass::Lift),
+    (90,  LineClass::Door),
+    (97,  LineClass::Teleporter),
+    (100, LineClass::Door),
+    (105, LineClass::SecretExit),
+    (106, LineClass::Stairs),
+    (107, LineClass::Stairs),
+];
+
+
+/// Strife specials, sorted by special number
+/// Strife keeps the Doom key door numbers but opens them with the ID card,
+/// pass card and ID badge, and adds doors of its own above 150 for the keys
+/// found along the way (brass, silver and gold, the base key and so on)
+const STRIFE_SPECIALS: &[(u16, LineClass)] = &[
+    (1,   LineClass::Door),
+    (2,   LineClass::Door),
+    (3,   LineClass::Door),
+    (4,   LineClass::Door),
+    (6,   LineClass::Crusher),
+    (7,   LineClass::Stairs),
+    (8,   LineClass::Stairs),
+    (9,   LineClass::Switch),
+    (10,  LineClass::Lift),
+    (11,  LineClass::Exit),
+    (14,  LineClass::Switch),
+    (15,  LineClass::Switch),
+    (16,  LineClass::Door),
+    (18,  LineClass::Switch),
+    (20,  LineClass::Switch),
+    (21,  LineClass::Lift),
+    (23,  LineClass::Switch),
+    (25,  LineClass::Crusher),
+    (26,  LineClass::LockedDoor(Key::IdCard)),
+    (27,  LineClass::LockedDoor(Key::PassCard)),
+    (28,  LineClass::LockedDoor(Key::IdBadge)),
+    (29,  LineClass::Door),
+    (31,  LineClass::Door),
+    (32,  LineClass::LockedDoor(Key::IdCard)),
+    (33,  LineClass::LockedDoor(Key::IdBadge)),
+    (34,  LineClass::LockedDoor(Key::PassCard)),
+    (39,  LineClass::Teleporter),
+    (41,  LineClass::Switch),
+    (42,  LineClass::Door),
+    (43,  LineClass::Switch),
+    (45,  LineClass::Switch),
+    (46,  LineClass::Door),
+    (49,  LineClass::Crusher),
+    (50,  LineClass::Door),
+    (51,  LineClass::SecretExit),
+    (52,  LineClass::Exit),
+    (55,  LineClass::Switch),
+    (57,  LineClass::Crusher),
+    (60,  LineClass::Switch),
+    (61,  LineClass::Door),
+    (62,  LineClass::Lift),
+    (63,  LineClass::Door),
+    (64,  LineClass::Switch),
+    (65,  LineClass::Switch),
+    (66,  LineClass::Switch),
+    (67,  LineClass::Switch),
+    (68,  LineClass::Switch),
+    (69,  LineClass::Switch),
+    (70,  LineClass::Switch),
+    (71,  LineClass::Switch),
+    (73,  LineClass::Crusher),
+    (74,  LineClass::Crusher),
+    (75,  LineClass::Door),
+    (76,  LineClass::Door),
+    (77,  LineClass::Crusher),
+    (86,  LineClass::Door),
+    (88,  LineClass::Lift),
+    (90,  LineClass::Door),
+    (97,  LineClass::Teleporter),
+    (100, LineClass::Stairs),
+    (101, LineClass::Switch),
+    (102, LineClass::Switch),
+    (103, LineClass::Door),
+    (105, LineClass::Door),
+    (106, LineClass::Door),
+    (107, LineClass::Door),
+    (108, LineClass::Door),
+    (109, LineClass::Door),
+    (110, LineClass::Door),
+    (111, LineClass::Door),
+    (112, LineClass::Door),
+    (113, LineClass::Door),
+    (114, LineClass::Door),
+    (115, LineClass::Door),
+    (116, LineClass::Door),
+    (117, LineClass::Door),
+    (118, LineClass::Door),
+    (120, LineClass::Lift),
+    (121, LineClass::Lift),
+    (122, LineClass::Lift),
+    (123, LineClass::Lift),
+    (124, LineClass::SecretExit),
+    (125, LineClass::Teleporter),
+    (126, LineClass::Teleporter),
+    (127, LineClass::Stairs),
+    (131, LineClass::Switch),
+    (132, LineClass::Switch),
+    (138, LineClass::Switch),
+    (139, LineClass::Switch),
+    (140, LineClass::Switch),
+    (141, LineClass::Crusher),
+    (156, LineClass::LockedDoor(Key::Brass)),
+    (157, LineClass::LockedDoor(Key::Brass)),
+    (158, LineClass::LockedDoor(Key::Silver)),
+    (159, LineClass::LockedDoor(Key::Silver)),
+    (160, LineClass::LockedDoor(Key::Gold)),
+    (161, LineClass::LockedDoor(Key::Gold)),
+    (169, LineClass::LockedDoor(Key::Base)),
+    (170, LineClass::LockedDoor(Key::Governor)),
+    (190, LineClass::LockedDoor(Key::Order)),
+    (213, LineClass::LockedDoor(Key::Chalice)),
+    (217, LineClass::LockedDoor(Key::Core)),
+    (224, LineClass::LockedDoor(Key::Chapel)),
+    (225, LineClass::LockedDoor(Key::Catacomb)),
+    (232, LineClass::LockedDoor(Key::OraclePass)),
+];
+
+
+/// Hexen action specials, sorted by special number
+/// Hexen has no switch specials of its own; any use-activated special
+/// that is not listed here is treated as a switch instead
+const HEXEN_SPECIALS: &[(u16, LineClass)] = &[
+    (10,  LineClass::Door),
+    (11,  LineClass::Door),
+    (12,  LineClass::Door),
+    (13,  LineClass::Door),
+    (26,  LineClass::Stairs),
+    (27,  LineClass::Stairs),
+    (31,  LineClass::Stairs),
+    (32,  LineClass::Stairs),
+    (42,  LineClass::Crusher),
+    (43,  LineClass::Crusher),
+    (44,  LineClass::Crusher),
+    (45,  LineClass::Crusher),
+    (60,  LineClass::Lift),
+    (61,  LineClass::Lift),
+    (62,  LineClass::Lift),
+    (63,  LineClass::Lift),
+    (64,  LineClass::Lift),
+    (65,  LineClass::Lift),
+    (70,  LineClass::Teleporter),
+    (71,  LineClass::Teleporter),
+    (74,  LineClass::Exit),
+    (75,  LineClass::Exit),
+];
+
+
+// look a special number up in a sorted special table
+fn lookup(table: &[(u16, LineClass)], special: u16) -> Option<LineClass> {
+    table
+        .binary_search_by_key(&special, |&(num, _)| num)
+        .ok()
+        .map(|i| table[i].1)
+}
+
+
+/// Hexen specials that ask for a key, and which argument holds the lock
+/// Door_LockedRaise keeps it in arg4, ACS_LockedExecute in arg5
+const HEXEN_LOCKED: &[(u16, usize)] = &[
+    (13, 4),
+    (83, 5),
+];
+
+
+// turn a Hexen-format lock number into a Key, or None when no key is needed
+// Hexen numbers its eleven keys directly, while ZDoom's Hexen-format maps
+// for the other games use 1-6 for cards and skulls, 100 for any key, 101
+// and 229 for every key and 129-131 for either key of a color
+// locks neither knows are Unknown rather than guessed at
+fn hexen_lock(lock: u8, game: Game) -> Option<Key> {
+    if lock == 0 {
+        return None;
+    }
+    Some(match game {
+        Game::Hexen => match lock {
+            1  => Key::Steel,
+            2  => Key::Cave,
+            3  => Key::Axe,
+            4  => Key::Fire,
+            5  => Key::Emerald,
+            6  => Key::Dungeon,
+            7  => Key::Silver,
+            8  => Key::Rusted,
+            9  => Key::Horn,
+            10 => Key::Swamp,
+            11 => Key::Castle,
+            _  => Key::Unknown,
+        },
+        _ => match lock {
+            1 | 4 | 129 => match game {
+                Game::Heretic => Key::Green,
+                _             => Key::Red,
+            },
+            2 | 5 | 130 => Key::Blue,
+            3 | 6 | 131 => Key::Yellow,
+            100         => Key::Any,
+            101 | 229   => Key::All,
+            _           => Key::Unknown,
+        },
+    })
+}
+
+
+/// Sort a linedef into a LineClass using the special table for its game
+/// Hexen-format maps always use Hexen action specials, whatever the game
+pub fn classify(line: &LineDef, is_hexen: bool, game: Game) -> LineClass {
+    let special = line.special_type();
+    if special == 0 {
+        return LineClass::Normal;
+    }
+
+    if is_hexen {
+        // a lock of 0 needs no key, and the special is what it would be unlocked
+        if let Some(&(_, arg)) = HEXEN_LOCKED.iter().find(|&&(num, _)| num == special) {
+            if let Some(key) = hexen_lock(line.args[arg], game) {
+                return LineClass::LockedDoor(key);
+            }
+        }
+
+        return match lookup(HEXEN_SPECIALS, special) {
+            Some(class) => class,
+            None if line.is_use_activated() => LineClass::Switch,
+            None => LineClass::Normal,
+        };
+    }
+
+    match game {
+        Game::Heretic => lookup(HERETIC_SPECIALS, special).unwrap_or(LineClass::Normal),
+        Game::Strife  => lookup(STRIFE_SPECIALS, special).unwrap_or(LineClass::Normal),
+        _ => match generalized::decode(special) {
+            Some(gen) => generalized_class(gen),
+            None      => lookup(DOOM_SPECIALS, special).unwrap_or(LineClass::Normal),
+        },
+    }
+}
+
+
+// sort a decoded Boom generalized special into a LineClass
+// floor and ceiling movers only stand out when they are pressed like switches
+fn generalized_class(gen: generalized::Generalized) -> LineClass {
+    match gen.action {
+        Action::LockedDoor(lock) => LineClass::LockedDoor(lock.key()),
+        Action::Door             => LineClass::Door,
+        Action::Lift             => LineClass::Lift,
+        Action::Stairs           => LineClass::Stairs,
+        Action::Crusher          => LineClass::Crusher,
+        Action::Floor | Action::Ceiling => match gen.trigger.is_use() {
+            true => LineClass::Switch,
+            _    => LineClass::Normal,
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use doom::specials::*;
+
+    fn is_sorted(table: &[(u16, LineClass)]) -> bool {
+        table.windows(2).all(|w| w[0].0 < w[1].0)
+    }
+
+    #[test]
+    fn test_tables_sorted() {
+        assert!(is_sorted(DOOM_SPECIALS), "Doom special table out of order");
+        assert!(is_sorted(HERETIC_SPECIALS), "Heretic special table out of order");
+        assert!(is_sorted(STRIFE_SPECIALS), "Strife special table out of order");
+        assert!(is_sorted(HEXEN_SPECIALS), "Hexen special table out of order");
+    }
+
+    #[test]
+    fn test_doom_lookup() {
+        assert_eq!(lookup(DOOM_SPECIALS, 28), Some(LineClass::LockedDoor(Key::Red)));
+        assert_eq!(lookup(DOOM_SPECIALS, 11), Some(LineClass::Exit));
+        assert_eq!(lookup(DOOM_SPECIALS, 51), Some(LineClass::SecretExit));
+        assert_eq!(lookup(DOOM_SPECIALS, 97), Some(LineClass::Teleporter));
+        assert_eq!(lookup(DOOM_SPECIALS, 48), None);
+    }
+
+    #[test]
+    fn test_game_keys() {
+        assert_eq!(lookup(HERETIC_SPECIALS, 28), Some(LineClass::LockedDoor(Key::Green)));
+        assert_eq!(lookup(STRIFE_SPECIALS, 26), Some(LineClass::LockedDoor(Key::IdCard)));
+        assert_eq!(lookup(STRIFE_SPECIALS, 160), Some(LineClass::LockedDoor(Key::Gold)));
+        assert_eq!(hexen_lock(5, Game::Hexen), Some(Key::Emerald));
+        assert_eq!(hexen_lock(4, Game::Doom), Some(Key::Red));
+        assert_eq!(hexen_lock(1, Game::Heretic), Some(Key::Green));
+        assert_eq!(hexen_lock(0, Game::Doom), None);
+        assert_eq!(hexen_lock(42, Game::Doom), Some(Key::Unknown));
+        assert_eq!(hexen_lock(12, Game::Hexen), Some(Key::Unknown));
+    }
+}
+
+// end
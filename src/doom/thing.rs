@@ -0,0 +1,66 @@
+// thing.rs
+
+use utils::{u8_to_i16, u8_to_u16};
+use doom::constants::{DOOM_THING_WIDTH, HEXEN_THING_WIDTH};
+
+
+/// A Thing is anything placed on the map that isn't architecture: player
+/// starts, monsters, weapons, keys and decorations. Like LineDefs, the Thing
+/// size depends on whether it's a Hexen wad or not, as Hexen adds a thing ID,
+/// a height and an action special with arguments, which are all zero for
+/// a Doom-format thing.
+pub struct Thing {
+    pub tid:     u16,
+    pub x:       i16,
+    pub y:       i16,
+    pub z:       i16, // height above the floor
+    pub angle:   u16,
+    pub ttype:   u16,
+    pub flags:   u16,
+    pub special: u8,
+    pub args:    [u8; 5],
+}
+
+
+impl Thing {
+    pub fn new(is_hexen: bool, dat: &[u8]) -> Thing {
+        match is_hexen {
+            true => {
+                if dat.len() != HEXEN_THING_WIDTH {
+                    panic!("Thing not given {} bytes", HEXEN_THING_WIDTH);
+                }
+
+                Thing{
+                    tid:     u8_to_u16(dat[0],   dat[1]),
+                    x:       u8_to_i16(dat[2],   dat[3]),
+                    y:       u8_to_i16(dat[4],   dat[5]),
+                    z:       u8_to_i16(dat[6],   dat[7]),
+                    angle:   u8_to_u16(dat[8],   dat[9]),
+                    ttype:   u8_to_u16(dat[10], dat[11]),
+                    flags:   u8_to_u16(dat[12], dat[13]),
+                    special: dat[14],
+                    args:    [dat[15], dat[16], dat[17], dat[18], dat[19]],
+                }
+            }
+            _ => {
+                if dat.len() != DOOM_THING_WIDTH {
+                    panic!("Thing not given {} bytes", DOOM_THING_WIDTH);
+                }
+
+                Thing{
+                    tid:     0,
+                    x:       u8_to_i16(dat[0], dat[1]),
+                    y:       u8_to_i16(dat[2], dat[3]),
+                    z:       0,
+                    angle:   u8_to_u16(dat[4], dat[5]),
+                    ttype:   u8_to_u16(dat[6], dat[7]),
+                    flags:   u8_to_u16(dat[8], dat[9]),
+                    special: 0,
+                    args:    [0; 5],
+                }
+            }
+        }
+    }
+}
+
+// end
@@ -27,7 +27,7 @@ impl Vertex {
 
     // debugging purposes
     pub fn print(&self) {
-        println!("Vertex({}, {})", self.x, self.y);
+        say!("Vertex({}, {})", self.x, self.y);
     }
 }
 
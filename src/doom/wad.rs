@@ -1,10 +1,13 @@
 // wad.rs
 
+use std::collections::HashMap;
 use std::ops::{Range, RangeFrom};
 use utils::u8_to_u32;
 use doom::constants::{HEADER_WIDTH, IWAD_NUMBER, PWAD_NUMBER};
 use doom::lump::Lump;
-use doom::level::Level;
+use doom::level::{Level, LevelLumps};
+use doom::game::Game;
+use doom::mapinfo::parse_titles;
 
 
 /// The WadHeader reads the first 12 bytes of the Wad file and shows us a
@@ -27,6 +30,98 @@ pub struct Wad {
     pub header:   WadHeader,
     pub levels:   Vec<Level>,
     pub is_hexen: bool,
+    pub game:     Game,
+    pub flats:    HashMap<String, (u8, u8, u8)>,
+    pub titles:   HashMap<String, String>,
+}
+
+
+/// Names of every lump that can follow a level marker
+pub const MAP_LUMPS: &[&str] = &[
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS",
+    "NODES", "SECTORS", "REJECT", "BLOCKMAP", "BEHAVIOR", "SCRIPTS",
+];
+
+
+// slice a lump's data out of the data pool
+// empty lumps are allowed to point anywhere (often at offset 0)
+fn lump_data<'a>(dat: &'a [u8], lump: &Lump) -> Result<&'a [u8], String> {
+    if lump.size == 0 {
+        return Ok(&[]);
+    }
+    if lump.posn < HEADER_WIDTH {
+        return Err(format!("Lump {} points into the Wad header", lump.name));
+    }
+    match dat.get(lump.range()) {
+        Some(slice) => Ok(slice),
+        None        => Err(format!("Lump {} lies outside of the Wad", lump.name)),
+    }
+}
+
+
+// average the colors of every flat in the Wad through the first PLAYPAL
+// palette, for drawing floors in roughly their in-game color
+// flats sit between F_START and F_END (FF_START/FF_END in PWADs) and are
+// 64x64 palette indices. Wads without a palette give back an empty map.
+fn flat_colors(lumps: &[Lump], dat: &[u8]) -> HashMap<String, (u8, u8, u8)> {
+    let mut colors = HashMap::new();
+
+    let palette = match lumps.iter().find(|l| l.name == "PLAYPAL") {
+        Some(lump) => match lump_data(dat, lump) {
+            Ok(pal) if pal.len() >= 768 => pal,
+            _                           => { return colors; }
+        },
+        None => { return colors; }
+    };
+
+    let mut in_flats = false;
+    for lump in lumps {
+        match lump.name.as_str() {
+            "F_START" | "FF_START" => { in_flats = true; continue; }
+            "F_END"   | "FF_END"   => { in_flats = false; continue; }
+            _ => {}
+        }
+
+        if !in_flats || lump.size != 4096 {
+            continue;
+        }
+
+        if let Ok(pixels) = lump_data(dat, lump) {
+            let mut sum = (0u32, 0u32, 0u32);
+            for &px in pixels {
+                let i = px as usize * 3;
+                sum.0 += palette[i] as u32;
+                sum.1 += palette[i + 1] as u32;
+                sum.2 += palette[i + 2] as u32;
+            }
+            let n = pixels.len() as u32;
+            colors.insert(
+                lump.name.to_owned(),
+                ((sum.0 / n) as u8, (sum.1 / n) as u8, (sum.2 / n) as u8),
+            );
+        }
+    }
+    colors
+}
+
+
+// gather level titles from every MAPINFO-style lump in the Wad
+// lumps later in the Wad win, the same way a port would load them
+fn level_titles(lumps: &[Lump], dat: &[u8]) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    for lump in lumps {
+        match lump.name.as_str() {
+            "MAPINFO" | "ZMAPINFO" | "UMAPINFO" => {}
+            _ => { continue; }
+        }
+
+        if let Ok(text) = lump_data(dat, lump) {
+            for (name, title) in parse_titles(&String::from_utf8_lossy(text)) {
+                titles.insert(name, title);
+            }
+        }
+    }
+    titles
 }
 
 
@@ -59,10 +154,10 @@ impl WadHeader {
     }
 
     pub fn print(&self) {
-        println!("Wad Number:   {}", self.wadtype);
-        println!("Num Lumps:    {}", self.numlumps);
-        println!("Lump Address: {}", self.lumpaddr);
-        println!("Type of file: {}",
+        say!("Wad Number:   {}", self.wadtype);
+        say!("Num Lumps:    {}", self.numlumps);
+        say!("Lump Address: {}", self.lumpaddr);
+        say!("Type of file: {}",
                  match self.wadtype {
                      IWAD_NUMBER => "IWAD",
                      PWAD_NUMBER => "PWAD",
@@ -79,50 +174,54 @@ impl Wad {
         hd: WadHeader,
         lumps: &Vec<Lump>,
         dat: &[u8],
-        is_h: bool
+        is_h: bool,
+        game: Game,
     ) -> Result<Wad, String> {
         if lumps.len() == 0 {
             return Err(format!("No Lumps given to Wad::new()"));
         }
 
-        let mut levels        : Vec<Level> = Vec::new();
-        let mut data_count    : usize      = 0;
-        let mut current_level : &Lump      = &lumps[0];
-        let mut current_verts : &Lump      = &lumps[0];
-        let mut current_lines : &Lump      = &lumps[0];
-
-        // account for BEHAVIORS lumps (we're not quite there yet)
-        let data_count_target = match is_h {
-            true  => 3,
-            false => 3,
-        };
-
-        for lump in lumps {
-            if lump.is_level {
-                current_level = lump;
-                data_count += 1;
-            } else {
-                match lump.name.as_str() {
-                    "VERTEXES" => { current_verts = lump; data_count += 1; }
-                    "LINEDEFS" => { current_lines = lump; data_count += 1; }
-                    "THINGS"   => {}
-                    "SECTORS"  => {}
-                    "SSECTORS" => {}
-                    "SIDEDEFS" => {}
+        let mut levels : Vec<Level> = Vec::new();
+
+        // a level is its marker lump followed by the map lumps that belong
+        // to it, so scan forward from every marker until a lump is found
+        // that isn't part of a map
+        for (i, lump) in lumps.iter().enumerate() {
+            if !lump.is_level {
+                continue;
+            }
+
+            let mut raw = LevelLumps{
+                things:   &[],
+                vertexes: &[],
+                linedefs: &[],
+                sidedefs: &[],
+                sectors:  &[],
+            };
+
+            for map_lump in lumps[(i + 1)..].iter() {
+                if !MAP_LUMPS.contains(&map_lump.name.as_str()) {
+                    break;
+                }
+
+                let slice = lump_data(dat, map_lump)?;
+                match map_lump.name.as_str() {
+                    "THINGS"   => { raw.things   = slice; }
+                    "VERTEXES" => { raw.vertexes = slice; }
+                    "LINEDEFS" => { raw.linedefs = slice; }
+                    "SIDEDEFS" => { raw.sidedefs = slice; }
+                    "SECTORS"  => { raw.sectors  = slice; }
                     _          => {}
                 }
             }
 
-            if data_count == data_count_target {
-                let l = Level::new(
-                    &current_level.name,
-                    &dat[current_verts.range()],
-                    &dat[current_lines.range()],
-                    is_h,
-                );
-                levels.push(l);
-                data_count = 0;
+            // markers without any geometry (like a stray MAPINFO-style lump
+            // that happens to look like a map name) aren't levels
+            if raw.vertexes.is_empty() || raw.linedefs.is_empty() {
+                continue;
             }
+
+            levels.push(Level::new(&lump.name, &raw, is_h));
         }
 
         Ok(Wad{
@@ -130,12 +229,16 @@ impl Wad {
             header:   hd,
             levels:   levels,
             is_hexen: is_h,
+            game:     game,
+            flats:    flat_colors(lumps, dat),
+            titles:   level_titles(lumps, dat),
         })
     }
 
     pub fn print_info(&self) {
-        println!("Wad name: {}", self.name);
-        println!("Level count: {}", self.levels.len());
+        say!("Wad name: {}", self.name);
+        say!("Game: {}", self.game.name());
+        say!("Level count: {}", self.levels.len());
     }
 
     pub fn print_level_info(&self) {
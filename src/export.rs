@@ -0,0 +1,292 @@
+// export.rs
+
+/// Export writes the parsed model of every level as JSON, for tools that
+/// want a level's data without reading WADs themselves: its vertices,
+/// linedefs (with their flags and specials decoded), sidedefs, sectors
+/// and things, along with a little about the level and the WAD it's from.
+///
+/// Records refer to each other by index, the same way the WAD does, and
+/// a missing sidedef is null rather than -1.
+
+use std::fs::write;
+use json::Json;
+use optparse::Options;
+use output::{output_path, prepare};
+use mapfilter::select_levels;
+use report::FileReport;
+use doom::wad::Wad;
+use doom::level::Level;
+use doom::linedef::LineDef;
+use doom::specials::{classify, LineClass};
+use doom::generalized::{self, Action};
+use doom::game::Game;
+
+
+// linedef flag bits and their names, shared by the Doom and Hexen formats
+const LINE_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "impassable"),
+    (0x0002, "block-monsters"),
+    (0x0004, "two-sided"),
+    (0x0008, "upper-unpegged"),
+    (0x0010, "lower-unpegged"),
+    (0x0020, "secret"),
+    (0x0040, "block-sound"),
+    (0x0080, "hidden"),
+    (0x0100, "mapped"),
+];
+
+
+// the names of every flag set on a line, Hexen adds a repeatable bit
+fn flag_names(line: &LineDef, is_hexen: bool) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = LINE_FLAGS
+        .iter()
+        .filter(|&&(bit, _)| line.flags & bit != 0)
+        .map(|&(_, name)| name)
+        .collect();
+    if is_hexen && line.flags & 0x0200 != 0 {
+        names.push("repeatable");
+    }
+    names
+}
+
+
+// a side index, or null for a side that isn't there
+fn side(index: i16) -> Json {
+    match index {
+        i if i < 0 => Json::Null,
+        i          => Json::from(i as i64),
+    }
+}
+
+
+// what a line's special does, decoded as far as the game allows
+fn special_json(line: &LineDef, wad: &Wad) -> Json {
+    let special = line.special_type();
+    if special == 0 {
+        return Json::Null;
+    }
+
+    let class = classify(line, wad.is_hexen, wad.game);
+    let mut out = Json::object()
+        .set("number", special as i64)
+        .set("kind", class.name());
+    if let LineClass::LockedDoor(key) = class {
+        out = out.set("key", key.name());
+    }
+
+    // Boom's generalized specials only exist in Doom-format maps
+    let boom = !wad.is_hexen && wad.game != Game::Heretic && wad.game != Game::Strife;
+    if let Some(gen) = generalized::decode(special).filter(|_| boom) {
+        let mut decoded = Json::object()
+            .set("trigger", format!("{:?}", gen.trigger))
+            .set("speed", format!("{:?}", gen.speed));
+        decoded = match gen.action {
+            Action::LockedDoor(lock) => {
+                decoded.set("action", "LockedDoor").set("lock", format!("{:?}", lock))
+            }
+            action => decoded.set("action", format!("{:?}", action)),
+        };
+        out = out.set("generalized", decoded);
+    }
+    out
+}
+
+
+// the whole parsed model of one level
+pub fn level_to_json(lev: &Level, wad: &Wad) -> Json {
+    let vertices: Vec<Json> = lev.vertices
+        .iter()
+        .map(|v| Json::object().set("x", v.x as i64).set("y", v.y as i64))
+        .collect();
+
+    let linedefs: Vec<Json> = lev.linedefs
+        .iter()
+        .map(|l| {
+            let mut line = Json::object()
+                .set("start", l.start)
+                .set("end", l.end)
+                .set("front", side(l.right))
+                .set("back", side(l.left))
+                .set("flags", l.flags as i64)
+                .set("flag_names", flag_names(l, wad.is_hexen))
+                .set("special", special_json(l, wad));
+            // the first hexen byte is the special itself, the five args follow it
+            line = match wad.is_hexen {
+                true => line.set("args", l.args[1..].iter().map(|&a| a as i64).collect::<Vec<i64>>()),
+                _    => line.set("tag", l.tag as i64),
+            };
+            line
+        })
+        .collect();
+
+    let sidedefs: Vec<Json> = lev.sidedefs
+        .iter()
+        .map(|s| {
+            Json::object()
+                .set("x_offset", s.x_offset as i64)
+                .set("y_offset", s.y_offset as i64)
+                .set("upper", s.upper_tex.as_str())
+                .set("middle", s.middle_tex.as_str())
+                .set("lower", s.lower_tex.as_str())
+                .set("sector", s.sector as i64)
+        })
+        .collect();
+
+    let sectors: Vec<Json> = lev.sectors
+        .iter()
+        .map(|s| {
+            Json::object()
+                .set("floor", s.floor as i64)
+                .set("ceiling", s.ceil as i64)
+                .set("floor_flat", s.floor_tex.as_str())
+                .set("ceiling_flat", s.ceil_tex.as_str())
+                .set("light", s.light as i64)
+                .set("special", s.stype as i64)
+                .set("tag", s.stag as i64)
+                .set("secret", s.is_secret(wad.is_hexen))
+                .set("damage", s.damage(wad.is_hexen) as i64)
+                .set("liquid", s.liquid().map(|l| format!("{:?}", l).to_lowercase()))
+        })
+        .collect();
+
+    let things: Vec<Json> = lev.things
+        .iter()
+        .map(|t| {
+            let thing = Json::object()
+                .set("x", t.x as i64)
+                .set("y", t.y as i64)
+                .set("angle", t.angle as i64)
+                .set("type", t.ttype as i64)
+                .set("flags", t.flags as i64);
+            // only hexen things have an id, a height and a special
+            match wad.is_hexen {
+                true => thing
+                    .set("tid", t.tid as i64)
+                    .set("z", t.z as i64)
+                    .set("special", t.special as i64)
+                    .set("args", t.args.iter().map(|&a| a as i64).collect::<Vec<i64>>()),
+                _ => thing,
+            }
+        })
+        .collect();
+
+    let bounds = lev.bounds().map(|(x1, y1, x2, y2)| {
+        Json::object()
+            .set("min_x", x1 as i64)
+            .set("min_y", y1 as i64)
+            .set("max_x", x2 as i64)
+            .set("max_y", y2 as i64)
+    });
+
+    Json::object()
+        .set("name", lev.name.as_str())
+        .set("title", wad.titles.get(&lev.name).cloned())
+        .set("wad", wad.name.as_str())
+        .set("game", wad.game.name())
+        .set("format", match wad.is_hexen { true => "hexen", _ => "doom" })
+        .set("bounds", bounds)
+        .set("vertices", vertices)
+        .set("linedefs", linedefs)
+        .set("sidedefs", sidedefs)
+        .set("sectors", sectors)
+        .set("things", things)
+}
+
+
+// a level's JSON, on one line with --compact
+fn json_text(lev: &Level, wad: &Wad, opts: &Options) -> String {
+    match opts.compact {
+        true => level_to_json(lev, wad).to_string(),
+        _    => level_to_json(lev, wad).pretty(),
+    }
+}
+
+
+// write every selected level of a WAD to its own JSON file, or the one
+// level asked for to stdout with --output -
+pub fn export_wad(fname: &str, wad: &Wad, opts: &Options, report: &mut FileReport) -> Result<u8, String> {
+    let levels = select_levels(&wad.levels, &opts.maps, fname);
+    if opts.to_stdout() {
+        return match levels.as_slice() {
+            [lev] => {
+                say!("{}", json_text(lev, wad, opts));
+                report.add_level(&lev.name, Some(String::from("-")), false);
+                Ok(0)
+            }
+            _ => Err(String::from("Err: --output - writes a single level, pick one with --map")),
+        };
+    }
+
+    for lev in levels {
+        let path = output_path(fname, &lev.name, "json", opts)?;
+        let shown = path.display().to_string();
+        if !prepare(&path, opts.existing)? {
+            if opts.verbose {
+                say!("Skipped {}, it already exists", shown);
+            }
+            report.add_level(&lev.name, Some(shown), true);
+            continue;
+        }
+
+        if let Err(why) = write(&path, json_text(lev, wad, opts) + "\n") {
+            return Err(format!("Couldn't write '{}': {}", shown, why));
+        }
+        if opts.verbose {
+            say!("Wrote {}", shown);
+        }
+        report.add_level(&lev.name, Some(shown), false);
+    }
+    Ok(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use export::*;
+
+    #[test]
+    fn test_flag_names() {
+        let dat = [0, 0, 1, 0, 0x25, 0x02, 0, 0, 0, 0, 0, 0, 0, 0];
+        let line = LineDef::new(false, &dat);
+        assert_eq!(flag_names(&line, false), vec!["impassable", "two-sided", "secret"]);
+        assert_eq!(flag_names(&line, true), vec!["impassable", "two-sided", "secret", "repeatable"]);
+        assert_eq!(side(-1).to_string(), "null");
+        assert_eq!(side(3).to_string(), "3");
+    }
+
+    #[test]
+    fn test_hexen_things() {
+        use std::collections::HashMap;
+        use doom::wad::WadHeader;
+        use doom::thing::Thing;
+
+        // thing id 7 of type 118 at (64, -32), 24 units up and facing
+        // east, which runs special 80 with args 1, 0, 2, 0, 0
+        let dat = [7, 0, 64, 0, 0xe0, 0xff, 24, 0, 0, 0, 118, 0, 7, 0, 80, 1, 0, 2, 0, 0];
+        let wad = Wad{
+            name:     String::from("hexen.wad"),
+            header:   WadHeader{ wadtype: 0, numlumps: 0, lumpaddr: 0 },
+            levels:   Vec::new(),
+            is_hexen: true,
+            game:     Game::Hexen,
+            flats:    HashMap::new(),
+            titles:   HashMap::new(),
+        };
+        let lev = Level{
+            name:     String::from("MAP01"),
+            things:   vec![Thing::new(true, &dat)],
+            vertices: Vec::new(),
+            linedefs: Vec::new(),
+            sidedefs: Vec::new(),
+            sectors:  Vec::new(),
+            hash:     0,
+        };
+
+        let json = level_to_json(&lev, &wad).to_string();
+        assert!(json.contains(
+            r#""things":[{"x":64,"y":-32,"angle":0,"type":118,"flags":7,"tid":7,"z":24,"special":80,"args":[1,0,2,0,0]}]"#
+        ));
+    }
+}
+
+// end
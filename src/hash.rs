@@ -0,0 +1,69 @@
+// hash.rs
+
+/// A 64-bit FNV-1a hash, small enough to carry around instead of a crate.
+/// It's used to notice when a level or the options it's drawn with have
+/// changed, not to guard against anyone, so a fast hash is all it needs.
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64  = 0x0000_0100_0000_01b3;
+
+
+pub struct Hasher {
+    state: u64,
+}
+
+
+impl Default for Hasher {
+    fn default() -> Hasher {
+        Hasher{ state: FNV_OFFSET }
+    }
+}
+
+
+impl Hasher {
+    pub fn new() -> Hasher {
+        Hasher::default()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> &mut Hasher {
+        for &b in bytes {
+            self.state ^= b as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+        self
+    }
+
+    // write a piece along with its length, so ("ab", "c") and ("a", "bc")
+    // don't hash the same
+    pub fn piece(&mut self, bytes: &[u8]) -> &mut Hasher {
+        let len = (bytes.len() as u64).to_le_bytes();
+        self.write(&len).write(bytes)
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+
+// a hash written the way the manifest stores it
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use hash::*;
+
+    #[test]
+    fn test_fnv() {
+        assert_eq!(to_hex(Hasher::new().finish()), "cbf29ce484222325");
+        assert_eq!(to_hex(Hasher::new().write(b"a").finish()), "af63dc4c8601ec8c");
+        assert_eq!(to_hex(Hasher::new().write(b"foobar").finish()), "85944171f73967e8");
+        assert!(Hasher::new().piece(b"ab").piece(b"c").finish()
+             != Hasher::new().piece(b"a").piece(b"bc").finish());
+    }
+}
+
+// end
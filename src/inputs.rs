@@ -0,0 +1,137 @@
+// inputs.rs
+
+/// The files a run works on. Files named on the command line are taken as
+/// they are, while directories are searched, along with the directories
+/// inside them, for files ending in .wad, .pk3 or .zip in any case. Zip
+/// archives are picked up too, but can't be read yet, so each one found
+/// fails like any other file that isn't a WAD.
+/// --include and --exclude narrow a search down with globs: a pattern with
+/// a '/' in it is matched against the path under the directory searched,
+/// any other against the file's name, and neither minds about case.
+///
+/// Directories are read in name order, so a run over the same tree always
+/// goes the same way. Links to directories aren't followed.
+
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use mapfilter::glob_match;
+use optparse::Options;
+
+
+// the extensions of the files a search picks up
+const EXTENSIONS: &[&str] = &["wad", "pk3", "zip"];
+
+
+pub struct Input {
+    pub path:   String,
+    pub subdir: Option<PathBuf>, // where it was found under a directory given
+}
+
+
+impl Input {
+    // the options to work on the file with; --mirror-tree puts what's written
+    // for a file found in a directory under the same folders in --output
+    pub fn options(&self, opts: &Options) -> Options {
+        let mut mine = opts.clone();
+        if let (true, Some(sub), Some(out)) = (opts.mirror_tree, &self.subdir, &opts.output) {
+            mine.output = Some(Path::new(out).join(sub).display().to_string());
+        }
+        mine
+    }
+}
+
+
+// whether a pattern matches a file, by the path it was found at
+fn pattern_matches(pattern: &str, relative: &Path) -> bool {
+    let target = match pattern.contains('/') {
+        true => relative.to_string_lossy().replace('\\', "/"),
+        _    => relative.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+    };
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let target: Vec<char> = target.to_lowercase().chars().collect();
+    glob_match(&pattern, &target)
+}
+
+
+// whether a file found in a search should be worked on
+fn wanted(relative: &Path, opts: &Options) -> bool {
+    let ext = relative
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    EXTENSIONS.contains(&ext.as_str())
+        && (opts.includes.is_empty() || opts.includes.iter().any(|p| pattern_matches(p, relative)))
+        && !opts.excludes.iter().any(|p| pattern_matches(p, relative))
+}
+
+
+// search a directory and every one inside it for files to work on
+fn search(root: &Path, dir: &Path, opts: &Options, found: &mut Vec<Input>, errors: &mut Vec<(String, String)>) {
+    let mut entries: Vec<_> = match read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+        Err(why)    => {
+            errors.push((dir.display().to_string(), format!("Couldn't read directory: {}", why)));
+            return;
+        }
+    };
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            search(root, &path, opts, found, errors);
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        if wanted(relative, opts) {
+            found.push(Input{
+                path:   path.display().to_string(),
+                subdir: relative.parent().map(|p| p.to_path_buf()),
+            });
+        }
+    }
+}
+
+
+// every file to work on, and the directories that couldn't be read
+pub fn expand(opts: &Options) -> (Vec<Input>, Vec<(String, String)>) {
+    let mut found = Vec::new();
+    let mut errors = Vec::new();
+    for name in &opts.files {
+        let path = Path::new(name);
+        if !path.is_dir() {
+            found.push(Input{ path: name.clone(), subdir: None });
+            continue;
+        }
+
+        let before = found.len();
+        search(path, path, opts, &mut found, &mut errors);
+        if found.len() == before {
+            warn!("Warning: no WADs found in {}", name);
+        }
+    }
+    (found, errors)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use inputs::*;
+
+    #[test]
+    fn test_wanted() {
+        let mut opts = Options::default();
+        assert!(wanted(Path::new("doom/DOOM2.WAD"), &opts));
+        assert!(wanted(Path::new("mods/maps.Pk3"), &opts));
+        assert!(!wanted(Path::new("doom/readme.txt"), &opts));
+
+        opts.includes = vec![String::from("doom*")];
+        opts.excludes = vec![String::from("old/*")];
+        assert!(wanted(Path::new("iwads/doom.wad"), &opts));
+        assert!(!wanted(Path::new("iwads/heretic.wad"), &opts));
+        assert!(!wanted(Path::new("old/iwads/doom.wad"), &opts));
+    }
+}
+
+// end
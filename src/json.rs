@@ -0,0 +1,189 @@
+// json.rs
+
+/// A small JSON writer for the reports the program prints. A value is
+/// built up as a tree of Json nodes, with From conversions for the plain
+/// Rust types and a builder for objects, then written out either on one
+/// line or indented for people to read.
+///
+/// Objects keep their keys in the order they're set, so output is stable.
+
+use std::fmt;
+
+
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+
+impl Json {
+    pub fn object() -> Json {
+        Json::Object(Vec::new())
+    }
+
+    // set a key on an object, replacing any value it already has
+    pub fn set<V: Into<Json>>(mut self, key: &str, value: V) -> Json {
+        if let Json::Object(ref mut fields) = self {
+            let value = value.into();
+            match fields.iter().position(|f| f.0 == key) {
+                Some(i) => { fields[i].1 = value; }
+                None    => { fields.push((key.to_owned(), value)); }
+            }
+        }
+        self
+    }
+
+    // the value written over indented lines, two spaces a level
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(0));
+        out
+    }
+
+    // write the value out, on one line when indent is None
+    fn write(&self, out: &mut String, indent: Option<usize>) {
+        let inner = indent.map(|i| i + 1);
+        let (open_line, close_line) = match indent {
+            Some(i) => (format!("\n{}", "  ".repeat(i + 1)), format!("\n{}", "  ".repeat(i))),
+            None    => (String::new(), String::new()),
+        };
+
+        match *self {
+            Json::Null           => out.push_str("null"),
+            Json::Bool(b)        => out.push_str(if b { "true" } else { "false" }),
+            Json::Int(n)         => out.push_str(&n.to_string()),
+            Json::Num(n) if n.is_finite() => out.push_str(&n.to_string()),
+            Json::Num(_)         => out.push_str("null"),
+            Json::Str(ref s)     => quote(out, s),
+            Json::Array(ref items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&open_line);
+                    item.write(out, inner);
+                }
+                if !items.is_empty() {
+                    out.push_str(&close_line);
+                }
+                out.push(']');
+            }
+            Json::Object(ref fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&open_line);
+                    quote(out, key);
+                    out.push_str(match indent { Some(_) => ": ", None => ":" });
+                    value.write(out, inner);
+                }
+                if !fields.is_empty() {
+                    out.push_str(&close_line);
+                }
+                out.push('}');
+            }
+        }
+    }
+}
+
+
+// write a string in quotes, escaping what JSON asks to be escaped
+fn quote(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+
+// the value written on one line
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, None);
+        f.write_str(&out)
+    }
+}
+
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json { Json::Bool(b) }
+}
+
+impl From<i64> for Json {
+    fn from(n: i64) -> Json { Json::Int(n) }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json { Json::Int(n as i64) }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Json { Json::Num(n) }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(s: &'a str) -> Json { Json::Str(s.to_owned()) }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json { Json::Str(s) }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(v: Option<T>) -> Json {
+        match v {
+            Some(v) => v.into(),
+            None    => Json::Null,
+        }
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Json {
+        Json::Array(items.into_iter().map(|i| i.into()).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use json::*;
+
+    #[test]
+    fn test_write_json() {
+        let value = Json::object()
+            .set("file", "maps/\"odd\".wad")
+            .set("ok", false)
+            .set("levels", vec![Json::object().set("name", "MAP01").set("size", 1.5)])
+            .set("output", None::<String>)
+            .set("errors", Vec::<String>::new());
+        assert_eq!(
+            value.to_string(),
+            "{\"file\":\"maps/\\\"odd\\\".wad\",\"ok\":false,\"levels\":[{\"name\":\"MAP01\",\"size\":1.5}],\"output\":null,\"errors\":[]}"
+        );
+        assert_eq!(
+            Json::object().set("a", vec![1i64, 2]).pretty(),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+        );
+    }
+}
+
+// end
@@ -0,0 +1,173 @@
+// legend.rs
+
+/// A Legend is the panel drawn underneath a map: a few lines of title text,
+/// an optional scale bar, and one entry for every style of line or floor
+/// used in the map. It is laid out in its own strip of the viewbox so it
+/// never covers any of the map itself, and is painted by stylesheet rules
+/// like the rest of the map, apart from swatches with no class to follow.
+
+use svg::*;
+
+
+/// What gets drawn next to an entry's label
+#[derive(Clone, Copy)]
+pub enum Swatch {
+    Line(f64, bool), // stroke width, and whether the line is dashed
+    Fill,
+}
+
+
+pub struct LegendEntry {
+    pub label:  String,
+    pub class:  Option<String>, // the class of what it stands for, painted the same
+    pub color:  Color,          // the paint of a swatch with no class
+    pub swatch: Swatch,
+}
+
+
+#[derive(Default)]
+pub struct Legend {
+    pub titles:  Vec<String>,
+    pub scale:   bool,
+    pub entries: Vec<LegendEntry>,
+}
+
+
+// lengths offered for the scale bar, in map units
+const SCALE_STEPS: [f64; 8] = [64.0, 128.0, 256.0, 512.0, 1024.0, 2048.0, 4096.0, 8192.0];
+
+
+impl Legend {
+    pub fn new() -> Legend {
+        Legend::default()
+    }
+
+    pub fn add_title(&mut self, title: &str) {
+        self.titles.push(title.to_owned());
+    }
+
+    // add an entry unless one with the same label is already listed
+    pub fn add_entry(&mut self, label: &str, class: Option<&str>, color: Color, swatch: Swatch) {
+        if self.entries.iter().any(|e| e.label == label) {
+            return;
+        }
+        self.entries.push(LegendEntry{
+            label:  label.to_owned(),
+            class:  class.map(|c| c.to_owned()),
+            color:  color,
+            swatch: swatch,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.titles.is_empty() && self.entries.is_empty() && !self.scale
+    }
+
+    // entries are laid out in as many columns as fit across the map
+    fn columns(&self, width: f64, font: f64) -> usize {
+        (width / column_width(font)).max(1.0) as usize
+    }
+
+    // the height of the whole panel in viewbox units
+    pub fn height(&self, width: f64, font: f64) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let cols = self.columns(width, font);
+        let entry_rows = self.entries.len().div_ceil(cols);
+        let scale_rows = match self.scale { true => 1, _ => 0 };
+        let rows = self.titles.len() + scale_rows + entry_rows;
+        rows as f64 * row_height(font) + font / 2.0
+    }
+
+    // the stylesheet rules for the panel, the scale bar's stroke
+    // multiplied by k like the map's own lines
+    pub fn style(&self, fg: &Color, k: f64) -> String {
+        format!(
+            "#legend text {{ fill: {}; }}\n#legend .scale-bar {{ stroke: {}; stroke-width: {}; }}\n",
+            color_to_string(fg), color_to_string(fg), num(5.0 * k),
+        )
+    }
+
+    // draw the panel with its top-left corner at (x, y)
+    pub fn draw(&self, buf: &mut SVGGroup, (x, y): (f64, f64), width: f64, font: f64) {
+        let row = row_height(font);
+        let mut top = y;
+
+        for title in &self.titles {
+            buf.add_object(Box::new(SVGText::shape(x, top + font, font, title).with_class("title")));
+            top += row;
+        }
+
+        if self.scale {
+            let len = scale_length(width);
+            let mid = top + row / 2.0;
+            let tick = font / 3.0;
+            for &(x1, y1, x2, y2) in &[
+                (x, mid, x + len, mid),
+                (x, mid - tick, x, mid + tick),
+                (x + len, mid - tick, x + len, mid + tick),
+            ] {
+                buf.add_object(Box::new(SVGLine::shape(x1, y1, x2, y2).with_class("scale-bar")));
+            }
+            buf.add_object(Box::new(
+                SVGText::shape(x + len + font, mid + tick, font, &format!("{} map units", len))
+                    .with_class("scale-bar-label"),
+            ));
+            top += row;
+        }
+
+        let cols = self.columns(width, font);
+        for (i, entry) in self.entries.iter().enumerate() {
+            let ex = x + (i % cols) as f64 * column_width(font);
+            let ey = top + (i / cols) as f64 * row + row / 2.0;
+
+            match (entry.swatch, &entry.class) {
+                (Swatch::Line(_, _), Some(class)) => {
+                    buf.add_object(Box::new(
+                        SVGLine::shape(ex, ey, ex + font * 2.0, ey).with_class(&format!("line {}", class)),
+                    ));
+                }
+                (Swatch::Line(stroke, dashed), None) => {
+                    let line = SVGLine::new(ex, ey, ex + font * 2.0, ey, stroke, entry.color.clone());
+                    buf.add_object(Box::new(match dashed {
+                        true => line.dashed(font / 3.0),
+                        _    => line,
+                    }));
+                }
+                (Swatch::Fill, _) => {
+                    buf.add_object(Box::new(SVGRect::new(
+                        ex, ey - font / 2.0, font * 2.0, font, entry.color.clone(),
+                    )));
+                }
+            }
+
+            buf.add_object(Box::new(SVGText::shape(ex + font * 2.5, ey + font / 3.0, font, &entry.label)));
+        }
+    }
+}
+
+
+fn row_height(font: f64) -> f64 {
+    font * 1.5
+}
+
+
+fn column_width(font: f64) -> f64 {
+    font * 12.0
+}
+
+
+// pick the longest scale step that fits in a quarter of the map's width
+fn scale_length(width: f64) -> f64 {
+    let mut len = SCALE_STEPS[0];
+    for &step in SCALE_STEPS.iter() {
+        if step <= width / 4.0 {
+            len = step;
+        }
+    }
+    len
+}
+
+// end
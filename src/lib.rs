@@ -1,10 +1,26 @@
 // lib.rs
 
+#[macro_use]
+pub mod log;
+pub mod pool;
 pub mod svg;
 pub mod utils;
+pub mod json;
+pub mod hash;
+pub mod mapfilter;
+pub mod output;
+pub mod inputs;
+pub mod cache;
 pub mod optparse;
+pub mod legend;
+pub mod projection;
+pub mod optimize;
 pub mod mapmaker;
+pub mod export;
+pub mod watch;
 pub mod parse_wad;
+pub mod report;
+pub mod commands;
 pub mod doom;
 
 // end
@@ -0,0 +1,86 @@
+// log.rs
+
+/// Everything the library prints goes through say! and warn!, which print
+/// straight to stdout and stderr unless the thread is capturing. Jobs run
+/// on worker threads capture what they print, so it can be replayed in the
+/// order the jobs were given instead of the order they finished in.
+
+use std::cell::RefCell;
+use std::mem::replace;
+
+
+pub enum Line {
+    Out(String),
+    Err(String),
+}
+
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<Line>>> = const { RefCell::new(None) };
+}
+
+
+// print a line, or hold onto it if the thread is capturing
+pub fn emit(line: Line) {
+    let line = CAPTURED.with(|c| match *c.borrow_mut() {
+        Some(ref mut lines) => { lines.push(line); None }
+        None                => Some(line),
+    });
+    match line {
+        Some(Line::Out(s)) => println!("{}", s),
+        Some(Line::Err(s)) => eprintln!("{}", s),
+        None               => {}
+    }
+}
+
+
+// run f with everything it prints held back, handing the lines back with its result
+pub fn capture<R, F: FnOnce() -> R>(f: F) -> (R, Vec<Line>) {
+    let outer = CAPTURED.with(|c| c.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let lines = CAPTURED.with(|c| replace(&mut *c.borrow_mut(), outer));
+    (result, lines.unwrap_or_default())
+}
+
+
+// print captured lines, or pass them on if this thread is capturing too
+pub fn replay(lines: Vec<Line>) {
+    for line in lines {
+        emit(line);
+    }
+}
+
+
+macro_rules! say {
+    ()            => { $crate::log::emit($crate::log::Line::Out(String::new())) };
+    ($($arg:tt)*) => { $crate::log::emit($crate::log::Line::Out(format!($($arg)*))) };
+}
+
+
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::log::emit($crate::log::Line::Err(format!($($arg)*))) };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use log::*;
+
+    #[test]
+    fn test_capture() {
+        let (n, lines) = capture(|| {
+            say!("one {}", 1);
+            let (_, inner) = capture(|| warn!("two"));
+            replay(inner);
+            3
+        });
+        assert_eq!(n, 3);
+        let text: Vec<String> = lines
+            .into_iter()
+            .map(|l| match l { Line::Out(s) => s, Line::Err(s) => format!("!{}", s) })
+            .collect();
+        assert_eq!(text, vec!["one 1", "!two"]);
+    }
+}
+
+// end
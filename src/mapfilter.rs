@@ -0,0 +1,183 @@
+// mapfilter.rs
+
+/// A MapFilter picks levels out of a WAD by name, given on the command line
+/// with --map. A filter can be an exact name (MAP07), a glob with '*' and
+/// '?' wildcards (E2M*) or an inclusive range of names (MAP01-MAP10).
+///
+/// Ranges compare names piece by piece, with runs of digits compared as
+/// numbers, so E1M9 comes before E1M10 and MAP01-MAP10 covers ten levels.
+/// Only names with the same letters as the ends of the range are in it,
+/// which keeps E1M1-E1M9 from matching MAP05.
+
+use doom::level::Level;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapFilter {
+    Name(String),
+    Glob(String),
+    Range(String, String),
+}
+
+
+// a level name split into runs of letters and runs of digits
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Piece {
+    Text(String),
+    Num(u64),
+}
+
+
+fn pieces(name: &str) -> Vec<Piece> {
+    let mut out: Vec<Piece> = Vec::new();
+    let mut run = String::new();
+    for c in name.chars() {
+        if !run.is_empty() && run.chars().all(|r| r.is_ascii_digit()) != c.is_ascii_digit() {
+            out.push(to_piece(&run));
+            run.clear();
+        }
+        run.push(c);
+    }
+    if !run.is_empty() {
+        out.push(to_piece(&run));
+    }
+    out
+}
+
+
+fn to_piece(run: &str) -> Piece {
+    match run.parse::<u64>() {
+        Ok(n) => Piece::Num(n),
+        _     => Piece::Text(run.to_owned()),
+    }
+}
+
+
+// the letters of a name with its numbers left out, what a range's ends share
+fn shape(p: &[Piece]) -> Vec<Option<&str>> {
+    p.iter()
+        .map(|piece| match *piece {
+            Piece::Text(ref t) => Some(t.as_str()),
+            Piece::Num(_)      => None,
+        })
+        .collect()
+}
+
+
+// match a name against a pattern where '*' is any run of characters
+// (even none) and '?' is any one character
+pub fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None)           => true,
+        (Some('*'), _)         => {
+            glob_match(&pattern[1..], name)
+                || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_))   => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n))     => p == n && glob_match(&pattern[1..], &name[1..]),
+        _                      => false,
+    }
+}
+
+
+impl MapFilter {
+    // read a filter as given to --map, names are matched case-insensitively
+    pub fn parse(s: &str) -> Result<MapFilter, String> {
+        let s = s.trim().to_uppercase();
+        if s.is_empty() {
+            return Err(String::from("Err: Empty map filter"));
+        }
+
+        if s.contains('*') || s.contains('?') {
+            return Ok(MapFilter::Glob(s));
+        }
+
+        let ends: Vec<&str> = s.split('-').collect();
+        match ends.len() {
+            1 => Ok(MapFilter::Name(s.to_owned())),
+            2 if !ends[0].is_empty() && shape(&pieces(ends[0])) == shape(&pieces(ends[1])) => {
+                Ok(MapFilter::Range(ends[0].to_owned(), ends[1].to_owned()))
+            }
+            _ => Err(format!("Err: Couldn't parse '{}' to a range like MAP01-MAP10", s)),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.to_uppercase();
+        match *self {
+            MapFilter::Name(ref n) => *n == name,
+            MapFilter::Glob(ref g) => {
+                let pattern: Vec<char> = g.chars().collect();
+                let chars: Vec<char> = name.chars().collect();
+                glob_match(&pattern, &chars)
+            }
+            MapFilter::Range(ref lo, ref hi) => {
+                let (lo, hi, at) = (pieces(lo), pieces(hi), pieces(&name));
+                let (lo, hi) = match lo <= hi { true => (lo, hi), _ => (hi, lo) };
+                shape(&at) == shape(&lo) && lo <= at && at <= hi
+            }
+        }
+    }
+
+    // the filter written back the way it would be given
+    pub fn describe(&self) -> String {
+        match *self {
+            MapFilter::Name(ref n) | MapFilter::Glob(ref n) => n.to_owned(),
+            MapFilter::Range(ref lo, ref hi) => format!("{}-{}", lo, hi),
+        }
+    }
+}
+
+
+// pick the levels matched by any filter, or every level when there are no
+// filters, and warn about each filter that didn't match anything
+pub fn select_levels<'a>(levels: &'a [Level], filters: &[MapFilter], fname: &str) -> Vec<&'a Level> {
+    if filters.is_empty() {
+        return levels.iter().collect();
+    }
+
+    for filter in filters {
+        if !levels.iter().any(|lev| filter.matches(&lev.name)) {
+            warn!("Warning: --map {} matches no levels in {}", filter.describe(), fname);
+        }
+    }
+
+    levels
+        .iter()
+        .filter(|lev| filters.iter().any(|f| f.matches(&lev.name)))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use mapfilter::*;
+
+    #[test]
+    fn test_names_and_globs() {
+        let exact = MapFilter::parse("map07").unwrap();
+        assert!(exact.matches("MAP07"));
+        assert!(!exact.matches("MAP17"));
+
+        let glob = MapFilter::parse("E2M*").unwrap();
+        assert!(glob.matches("E2M1") && glob.matches("E2M10"));
+        assert!(!glob.matches("E1M2"));
+        assert!(MapFilter::parse("MAP?5").unwrap().matches("MAP25"));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let range = MapFilter::parse("MAP01-MAP10").unwrap();
+        assert!(range.matches("MAP01") && range.matches("MAP09") && range.matches("MAP10"));
+        assert!(!range.matches("MAP11") && !range.matches("E1M5"));
+
+        let range = MapFilter::parse("E1M8-E2M2").unwrap();
+        assert!(range.matches("E1M9") && range.matches("E2M1"));
+        assert!(!range.matches("E2M3"));
+
+        assert!(MapFilter::parse("MAP01-E1M1").is_err());
+        assert!(MapFilter::parse("MAP01-").is_err());
+    }
+}
+
+// end
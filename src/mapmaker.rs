@@ -1,123 +1,763 @@
 // mapmaker.rs
 // TODO: make the drawing algorithm a lot better
 
-use std::fs::create_dir;
+use std::cmp::max;
+use std::path::{Path, PathBuf};
 use svg::*;
-use optparse::Options;
+use legend::{Legend, Swatch};
+use projection::Projection;
+use optimize;
+use pool;
+use cache;
+use hash::{Hasher, to_hex};
+use optparse::{Options, Automap};
+use mapfilter::select_levels;
+use output::{output_path, prepare};
+use report::FileReport;
 use doom::linedef::*;
+use doom::specials::*;
 use doom::level::*;
+use doom::sector::Liquid;
 use doom::wad::*;
 
 
-// map a string (most likely a filepath for a wad) to a folder path string
-fn dir_name(dname: &str) -> String {
-    format!("{}.maps", dname)
-}
+// color used for a key that a locked door asks for
+fn key_color(key: Key) -> Color {
+    match key {
+        Key::Red    => Color::Red,
+        Key::Blue   => Color::Blue,
+        Key::Yellow => Color::Yellow,
+        Key::Green  => Color::Green,
+        Key::Any    => Color::Pink,
+        Key::All    => Color::Olive,
+
+        // Hexen keys are colored after their inventory icons
+        Key::Steel   => Color::Rgb(0x70, 0x80, 0x90),
+        Key::Cave    => Color::Rgb(0x8b, 0x5a, 0x2b),
+        Key::Axe     => Color::Rgb(0xb2, 0x22, 0x22),
+        Key::Fire    => Color::Rgb(0xff, 0x45, 0x00),
+        Key::Emerald => Color::Rgb(0x00, 0xa8, 0x6b),
+        Key::Dungeon => Color::Rgb(0x48, 0x3d, 0x8b),
+        Key::Silver  => Color::Rgb(0xa9, 0xa9, 0xa9),
+        Key::Rusted  => Color::Rgb(0xb7, 0x41, 0x0e),
+        Key::Horn    => Color::Rgb(0xd2, 0xb4, 0x8c),
+        Key::Swamp   => Color::Rgb(0x55, 0x6b, 0x2f),
+        Key::Castle  => Color::Rgb(0x1e, 0x3f, 0x66),
 
+        // Strife keys
+        Key::IdCard   => Color::Blue,
+        Key::PassCard => Color::Yellow,
+        Key::IdBadge  => Color::Red,
+        Key::Brass    => Color::Rgb(0xb5, 0xa6, 0x42),
+        Key::Gold     => Color::Rgb(0xff, 0xd7, 0x00),
+        Key::Base     => Color::Rgb(0x5f, 0x9e, 0xa0),
+        Key::Governor => Color::Rgb(0x80, 0x00, 0x80),
+        Key::Order    => Color::Rgb(0x8b, 0x00, 0x00),
+        Key::Chalice  => Color::Rgb(0xda, 0xa5, 0x20),
+        Key::Core     => Color::Rgb(0x00, 0x80, 0x80),
+        Key::Chapel   => Color::Rgb(0xcd, 0x85, 0x3f),
+        Key::Catacomb => Color::Rgb(0x69, 0x69, 0x69),
+        Key::OraclePass => Color::Rgb(0x99, 0x32, 0xcc),
 
-// create a directory and return whether it was made or not
-fn make_directory(dname: &str) -> bool {
-    match create_dir(format!("{}", dname)) {
-        Ok(_) => true,
-        _ => false,
+        // a lock nothing is known about
+        Key::Unknown  => Color::Rgb(0xc0, 0xc0, 0xc0),
     }
 }
 
 
-// map a file name (level name) to an output file location string
-fn make_path_str(dir: &str, lname: &str) -> String {
-    format!("{}/{}.svg", dir, lname)
+// color and stroke width used to draw each class of special line
+// returns None for classes that should be drawn as plain walls
+fn class_style(class: LineClass) -> Option<(Color, f64)> {
+    match class {
+        LineClass::Normal        => None,
+        LineClass::LockedDoor(k) => Some((key_color(k), 7.0)),
+        LineClass::Door          => Some((Color::Brown, 7.0)),
+        LineClass::Lift          => Some((Color::Cyan, 7.0)),
+        LineClass::Exit          => Some((Color::Lime, 9.0)),
+        LineClass::SecretExit    => Some((Color::Magenta, 9.0)),
+        LineClass::Teleporter    => Some((Color::Purple, 9.0)),
+        LineClass::Switch        => Some((Color::Orange, 7.0)),
+        LineClass::Crusher       => Some((Color::Maroon, 7.0)),
+        LineClass::Stairs        => Some((Color::Teal, 7.0)),
+    }
 }
 
 
-// flip a value in a certain axis
-// if the axis is set to zero, just return the initial value
-fn flatten(v: u64, m: u64) -> u64 {
-    match m == 0 {
-        true => v,
-        _    => m - v,
+// the name a class of special line goes by in the legend
+fn class_label(class: LineClass) -> String {
+    match class {
+        LineClass::Normal        => String::new(),
+        LineClass::LockedDoor(k) => format!("{} door", k.name()),
+        LineClass::Door          => String::from("Door"),
+        LineClass::Lift          => String::from("Lift"),
+        LineClass::Exit          => String::from("Exit"),
+        LineClass::SecretExit    => String::from("Secret exit"),
+        LineClass::Teleporter    => String::from("Teleporter"),
+        LineClass::Switch        => String::from("Switch"),
+        LineClass::Crusher       => String::from("Crusher"),
+        LineClass::Stairs        => String::from("Stairs"),
     }
 }
 
 
-// Given a line, determine it's color
-// Whether it's a key door, wall, or two-sided line
-fn line_color(line: &LineDef, color_doors: bool, inverted: bool) -> Color {
-    let is_one_sided = line.is_one_sided();
-    match color_doors {
-        false => match is_one_sided {
-            true => match inverted {
-                true => Color::White,
-                _    => Color::Black,
+// everything needed to draw one line, and what to call it in the legend
+#[derive(Clone)]
+struct LineLook {
+    color:  Color,
+    width:  f64,
+    dashed: bool,
+    label:  String,
+    class:  String, // the CSS class its paint is given under, once named
+}
+
+
+// the stylesheet rules for a map's lines, background and labels; lines
+// carry no paint of their own, so every look is restyled in one place,
+// in an editor or from an outer stylesheet, without re-rendering the map.
+// the overlays add rules for what they draw themselves
+fn map_style(named: &[LineLook], opts: &Options, dash: f64) -> String {
+    let background = match opts.inverted {
+        true => Color::Black,
+        _    => Color::White,
+    };
+
+    let mut css = String::from(".line { fill: none; stroke-linecap: butt; stroke-linejoin: miter; }\n");
+    css.push_str(&format!("#background .background {{ fill: {}; }}\n", color_to_string(&background)));
+    for look in named {
+        css.push_str(&format!(
+            ".{} {{ stroke: {}; stroke-width: {};{} }}\n",
+            look.class, color_to_string(&look.color), num(look.width),
+            match look.dashed {
+                true => format!(" stroke-dasharray: {};", num(dash)),
+                _    => String::new(),
             },
-            _    => Color::Grey,
+        ));
+    }
+    css.push_str("#labels .secret-label { fill: magenta; }\n");
+    css
+}
+
+
+// turn a name like "Red key" into a CSS class like "red-key", with a
+// dash for every run of characters a class name shouldn't hold
+fn css_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+
+// CSS classes describing what a line is, so a stylesheet can pick out
+// any kind of line, e.g. "line one-sided special-26 locked-door blue-key"
+fn line_classes(line: &LineDef, class: LineClass) -> String {
+    let mut classes = vec![String::from("line")];
+    classes.push(String::from(match line.is_one_sided() {
+        true => "one-sided",
+        _    => "two-sided",
+    }));
+
+    let special = line.special_type();
+    if special != 0 {
+        classes.push(format!("special-{}", special));
+    }
+
+    match class {
+        LineClass::Normal        => {}
+        LineClass::LockedDoor(k) => {
+            classes.push(String::from("locked-door"));
+            classes.push(css_name(k.name()));
+        }
+        _ => { classes.push(css_name(&class_label(class))); }
+    }
+
+    if line.is_secret() { classes.push(String::from("flag-secret")); }
+    if line.is_hidden() { classes.push(String::from("flag-hidden")); }
+    if line.is_mapped() { classes.push(String::from("flag-mapped")); }
+    classes.join(" ")
+}
+
+
+// the layers every map is drawn in, bottom to top
+const LAYER_BACKGROUND: usize = 0;
+const LAYER_GRID: usize       = 1;
+const LAYER_SECTORS: usize    = 2;
+const LAYER_TWO_SIDED: usize  = 3;
+const LAYER_WALLS: usize      = 4;
+const LAYER_SPECIAL: usize    = 5;
+const LAYER_LABELS: usize     = 7;
+const LAYER_LEGEND: usize     = 8;
+const LAYER_NAMES: [&str; 9] = [
+    "background", "grid", "sectors", "two-sided-lines",
+    "walls", "special-lines", "things", "labels", "legend",
+];
+
+
+// the layer a line is drawn in
+fn line_layer(line: &LineDef, class: LineClass) -> usize {
+    match (class, line.is_one_sided()) {
+        (LineClass::Normal, true) => LAYER_WALLS,
+        (LineClass::Normal, _)    => LAYER_TWO_SIDED,
+        _                         => LAYER_SPECIAL,
+    }
+}
+
+
+// Given a line, determine it's color and stroke width
+// Special lines are colored by class when --doors or --specials are set,
+// everything else is drawn as a wall or a two-sided line
+fn line_style(line: &LineDef, wad: &Wad, opts: &Options) -> LineLook {
+    let class = classify(line, wad.is_hexen, wad.game);
+    let wanted = match class {
+        LineClass::Normal        => false,
+        LineClass::LockedDoor(_) => opts.color_doors || opts.specials,
+        _                        => opts.specials,
+    };
+
+    if wanted {
+        if let Some((color, width)) = class_style(class) {
+            return LineLook{
+                color:  color,
+                width:  width,
+                dashed: false,
+                label:  class_label(class),
+                class:  String::new(),
+            };
+        }
+    }
+
+    plain_style(line.is_one_sided(), opts)
+}
+
+
+// color and stroke width of a wall or a two-sided line
+fn plain_style(one_sided: bool, opts: &Options) -> LineLook {
+    let (color, width, label) = match one_sided {
+        true => match opts.inverted {
+            true => (Color::White, 7.0, "Wall"),
+            _    => (Color::Black, 7.0, "Wall"),
         },
-        _ => match line.special_type() {
-            28 => Color::Red,    // red keycard
-            33 => Color::Red,    // red keycard stay open
-            26 => Color::Blue,   // blue keycard
-            32 => Color::Blue,   // blue keycard stay open
-            27 => Color::Yellow, // yellow keycard
-            34 => Color::Yellow, // yellow keycard stay open
-            _  => match is_one_sided {
-                // if it's not a key line, paint wall
-                true => match inverted {
-                    true => Color::White,
-                    _    => Color::Black,
-                },
-                _    => Color::Grey,
+        _    => (Color::Grey, 5.0, "Two-sided line"),
+    };
+    LineLook{color: color, width: width, dashed: false, label: label.to_owned(), class: String::new()}
+}
+
+
+// apply the automap's drawing rules on top of a line's normal style
+// returns None when the automap would not draw the line at all
+fn automap_style(line: &LineDef, wad: &Wad, opts: &Options) -> Option<LineLook> {
+    let look = line_style(line, wad, opts);
+    match opts.automap {
+        Automap::Off     => Some(look),
+        Automap::Spoiler => match line.is_hidden() || line.is_secret() {
+            true => Some(LineLook{
+                dashed: true,
+                label:  format!("{} (hidden)", look.label),
+                ..look
+            }),
+            _ => Some(look),
+        },
+        _ if line.is_hidden() => None,
+        mode => {
+            // the computer area map shows unseen lines in grey
+            if mode == Automap::Start && !line.is_mapped() {
+                let mut unseen = plain_style(false, opts);
+                unseen.label = String::from("Unmapped line");
+                return Some(unseen);
+            }
+
+            match line.is_secret() {
+                true => Some(plain_style(true, opts)),
+                _    => Some(look),
             }
         }
     }
 }
 
 
-// convert a &Level into an SVG Buffer
-// calculates a lot of numbers and converts LineDefs into SVGLine objects
-fn level_to_svg(lev: &Level, opts: &Options) -> SVG {
-    // iterate through all vertices to find min/max bounds
-    let mut min_x: i16 = 0; let mut min_y: i16 = 0;
-    let mut max_x: i16 = 0; let mut max_y: i16 = 0;
-    for vert in &lev.vertices {
-        if min_x == 0 && max_x == 0 && min_y == 0 && max_y == 0 {
-            // set the min/max vars to the first vertex
-            min_x = vert.x; max_x = vert.x;
-            min_y = vert.y; max_y = vert.y;
-        } else {
-            if vert.x > max_x {
-                max_x = vert.x;
-            } else if vert.x < min_x {
-                min_x = vert.x;
+// how a line relates to the level's secret areas
+#[derive(Debug, PartialEq)]
+enum SecretRole {
+    Outside,
+    Border,   // the line is part of a secret sector's outline
+    Entrance, // the line leads into a secret area
+}
+
+
+// sort a line into a SecretRole by the sectors on either side of it
+// any line with a secret sector on only one side leads into it, and so
+// does a secret door: a line flagged to look like a wall on the automap
+// that opens, or that borders a secret sector (the flag alone only hides
+// the line, and most flagged lines are plain walls)
+fn secret_role(line: &LineDef, lev: &Level, wad: &Wad) -> SecretRole {
+    let front = lev.side_sector(line.right).map(|s| s.is_secret(wad.is_hexen));
+    let back = lev.side_sector(line.left).map(|s| s.is_secret(wad.is_hexen));
+    let borders = front == Some(true) || back == Some(true);
+    let opens = matches!(
+        classify(line, wad.is_hexen, wad.game),
+        LineClass::Door | LineClass::LockedDoor(_) | LineClass::Lift
+    );
+
+    match (front, back) {
+        (Some(f), Some(b)) if f != b                => SecretRole::Entrance,
+        _ if line.is_secret() && (borders || opens) => SecretRole::Entrance,
+        _ if borders                                => SecretRole::Border,
+        _                                           => SecretRole::Outside,
+    }
+}
+
+
+// the color used for a liquid floor when the Wad has no palette to
+// average the real flat with (most PWADs borrow it from their IWAD)
+fn liquid_color(liquid: Liquid) -> (u8, u8, u8) {
+    match liquid {
+        Liquid::Nukage => (0x3c, 0xb4, 0x3c),
+        Liquid::Water  => (0x30, 0x60, 0xc0),
+        Liquid::Lava   => (0xe0, 0x50, 0x10),
+        Liquid::Blood  => (0xa0, 0x10, 0x10),
+        Liquid::Slime  => (0x70, 0x80, 0x30),
+    }
+}
+
+
+// fill liquid sectors with the color of their flat, and hatch over
+// damaging floors with a stripe color that gets redder with the damage
+fn add_hazards(buf: &mut SVG, layer: &mut SVGGroup, lev: &Level, wad: &Wad, view: &Projection, k: f64) {
+    let spacing = scaled(24.0, k);
+    buf.add_def(Box::new(SVGHatch::new("hazard-5", spacing, scaled(6.0, k), Color::Orange)));
+    buf.add_def(Box::new(SVGHatch::new("hazard-10", spacing, scaled(8.0, k), Color::Rgb(0xff, 0x45, 0x00))));
+    buf.add_def(Box::new(SVGHatch::new("hazard-20", spacing, scaled(10.0, k), Color::Red)));
+    buf.add_style("#sectors .liquid { fill-opacity: 0.6; }\n");
+    for hatch in &["hazard-5", "hazard-10", "hazard-20"] {
+        buf.add_style(&format!("#sectors .{} {{ fill: url(#{}); }}\n", hatch, hatch));
+    }
+
+    let loops = lev.sector_loops();
+    for (sector, outline) in lev.sectors.iter().zip(loops.iter()) {
+        let liquid = sector.liquid();
+        let damage = sector.damage(wad.is_hexen);
+        if outline.is_empty() || (liquid.is_none() && damage == 0) {
+            continue;
+        }
+
+        let trace = |path: &mut SVGPath| {
+            for verts in outline {
+                // a loop through a vertex that isn't there is left out
+                let corners: Option<Vec<(i32, i32)>> = verts
+                    .iter()
+                    .map(|&v| lev.vertices.get(v).map(|p| (p.x as i32, p.y as i32)))
+                    .collect();
+                let corners = match corners {
+                    Some(c) => c,
+                    None    => continue,
+                };
+                let clipped = view.clip_loop(&corners);
+                if clipped.is_empty() {
+                    continue;
+                }
+                for (i, &(x, y)) in clipped.iter().enumerate() {
+                    let (x, y) = view.to_view(x, y);
+                    match i {
+                        0 => path.move_to(x, y),
+                        _ => path.line_to(x, y),
+                    }
+                }
+                path.close();
             }
+        };
+
+        if let Some(liquid) = liquid {
+            let (r, g, b) = match wad.flats.get(&sector.floor_tex) {
+                Some(&rgb) => rgb,
+                None       => liquid_color(liquid),
+            };
+            let mut path = SVGPath::new(Color::Rgb(r, g, b))
+                .with_class(&format!("sector liquid {}", css_name(&format!("{:?}", liquid))));
+            trace(&mut path);
+            layer.add_object(Box::new(path));
+        }
+
+        if damage > 0 {
+            let hatch = match damage {
+                0..=5  => "hazard-5",
+                6..=10 => "hazard-10",
+                _      => "hazard-20",
+            };
+            let mut path = SVGPath::new(Color::None)
+                .styled()
+                .with_class(&format!("sector damage {}", hatch));
+            trace(&mut path);
+            layer.add_object(Box::new(path));
+        }
+    }
+}
+
+
+// rank legend entries so walls and two-sided lines always come first
+fn legend_rank(label: &str) -> u8 {
+    match label {
+        "Wall"           => 0,
+        "Two-sided line" => 1,
+        _                => 2,
+    }
+}
+
+
+// the name of the WAD's file, without the path it was given by
+fn wad_file_name(wad: &Wad) -> String {
+    Path::new(&wad.name)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| wad.name.to_owned())
+}
+
+
+// build the legend drawn under a map
+// --legend gives a title block, a scale bar and an entry for every style
+// in use; otherwise coloring doors still lists the keys they ask for
+fn build_legend(lev: &Level, wad: &Wad, opts: &Options, looks: &[Option<LineLook>]) -> Legend {
+    let mut legend = Legend::new();
+
+    if !opts.legend {
+        if opts.color_doors || opts.specials {
+            let mut keys: Vec<Key> = lev.linedefs.iter().filter_map(|l| {
+                match classify(l, wad.is_hexen, wad.game) {
+                    LineClass::LockedDoor(key) => Some(key),
+                    _                          => None,
+                }
+            }).collect();
+            keys.sort();
+            keys.dedup();
 
-            if vert.y > max_y {
-                max_y = vert.y;
-            } else if vert.y < min_y {
-                min_y = vert.y;
+            for key in keys {
+                let class = LineClass::LockedDoor(key);
+                legend.add_entry(&class_label(class), None, key_color(key), Swatch::Line(scaled(7.0, stroke_factor(opts)), false));
             }
         }
+        return legend;
     }
 
-    // determine the shift offset to keep everything in 0..65535 range
-    let shift_x = 0 - min_x as i32;
-    let shift_y = 0 - min_y as i32;
+    match wad.titles.get(&lev.name) {
+        Some(title) => legend.add_title(&format!("{}: {}", lev.name, title)),
+        None        => legend.add_title(&lev.name),
+    }
+    legend.add_title(&format!("{} ({})", wad_file_name(wad), wad.game.name()));
+    legend.add_title(&format!(
+        "{} linedefs, {} sectors, {} things",
+        lev.linedefs.len(), lev.sectors.len(), lev.things.len(),
+    ));
+    legend.scale = true;
 
-    // padding from the edge of the image
-    let padding : u64 = 50;
+    let mut drawn: Vec<&LineLook> = looks.iter().filter_map(|l| l.as_ref()).collect();
+    drawn.sort_by_key(|l| legend_rank(&l.label));
+    for look in drawn {
+        legend.add_entry(&look.label, Some(&look.class), look.color.clone(), Swatch::Line(look.width, look.dashed));
+    }
 
-    // numbers that define the max X and Y ranges
-    let mx = (max_x as i32) + shift_x;
-    let my = (max_y as i32) + shift_y;
+    if opts.hazards {
+        if let Some(sector) = lev.sectors.iter().find(|s| s.liquid().is_some()) {
+            let (r, g, b) = match (wad.flats.get(&sector.floor_tex), sector.liquid()) {
+                (Some(&rgb), _)       => rgb,
+                (None, Some(liquid))  => liquid_color(liquid),
+                (None, None)          => (0, 0, 0),
+            };
+            legend.add_entry("Liquid floor", None, Color::Rgb(r, g, b), Swatch::Fill);
+        }
+        if lev.sectors.iter().any(|s| s.damage(wad.is_hexen) > 0) {
+            legend.add_entry("Damaging floor", None, Color::Pattern(String::from("hazard-20")), Swatch::Fill);
+        }
+    }
+
+    legend
+}
+
+
+// the scale that stroke widths are designed for, 1 px per 4 map units
+const REFERENCE_SCALE: f64 = 0.25;
+
+
+// how much stroke widths get multiplied by; with a fixed --scale every
+// stroke stays as wide on screen as it is at the reference scale
+fn stroke_factor(opts: &Options) -> f64 {
+    match opts.scale {
+        Some(scale) => REFERENCE_SCALE / scale,
+        None        => 1.0,
+    }
+}
+
+
+fn scaled(width: f64, k: f64) -> f64 {
+    width * k
+}
+
+
+// the level coordinates of every multiple of step within lo..=hi
+fn grid_stops(lo: i32, hi: i32, step: u64) -> Vec<i32> {
+    let step = step as i32;
+    let first = match lo % step {
+        0 => lo,
+        r if lo < 0 => lo - r,
+        r => lo - r + step,
+    };
+    (0..).map(|i| first + i * step).take_while(|&v| v <= hi).collect()
+}
+
+
+// draw grid lines every `spacing` level units, lined up on level
+// coordinate 0 the same way the blockmap and map editors line theirs up
+fn add_grid(buf: &mut SVG, layer: &mut SVGGroup, view: &Projection, spacing: u64, stroke: f64, inverted: bool) {
+    let color = match inverted {
+        true => Color::Rgb(0x40, 0x40, 0x40),
+        _    => Color::Rgb(0xd0, 0xd0, 0xd0),
+    };
+    buf.add_style(&format!(
+        "#grid .grid {{ stroke: {}; stroke-width: {}; }}\n", color_to_string(&color), num(stroke),
+    ));
+
+    for x in grid_stops(view.min_x, view.max_x, spacing) {
+        let (x1, y1) = view.to_view(x, view.max_y);
+        let (x2, y2) = view.to_view(x, view.min_y);
+        layer.add_object(Box::new(SVGLine::shape(x1, y1, x2, y2).with_class("grid")));
+    }
+    for y in grid_stops(view.min_y, view.max_y, spacing) {
+        let (x1, y1) = view.to_view(view.min_x, y);
+        let (x2, y2) = view.to_view(view.max_x, y);
+        layer.add_object(Box::new(SVGLine::shape(x1, y1, x2, y2).with_class("grid")));
+    }
+}
+
+
+// label level coordinates along the top and left edges of the map
+// labels follow the grid when there is one, skipping grid lines
+// whenever the labels would otherwise run into each other
+// a map turned at an odd angle has no edge an axis runs along, so it gets none
+fn add_axes(buf: &mut SVG, layer: &mut SVGGroup, view: &Projection, grid: u64, font: f64, fg: &Color, k: f64) {
+    let turns = match view.quarter_turns() {
+        Some(turns) => turns,
+        None        => { return; }
+    };
+    buf.add_style(&format!(
+        "#grid line.axis {{ stroke: {}; stroke-width: {}; }}\n#grid text.axis {{ fill: {}; }}\n",
+        color_to_string(fg), num(scaled(2.0, k)), color_to_string(fg),
+    ));
+
+    let mut step = match grid {
+        0 => 64,
+        _ => grid,
+    };
+    while (step as f64) < font * 4.0 {
+        step *= 2;
+    }
+
+    // where each label sits along the edge of the viewbox
+    let xs: Vec<(f64, f64, i32)> = grid_stops(view.min_x, view.max_x, step)
+        .into_iter()
+        .map(|x| { let (vx, vy) = view.to_view(x, view.min_y); (vx, vy, x) })
+        .collect();
+    let ys: Vec<(f64, f64, i32)> = grid_stops(view.min_y, view.max_y, step)
+        .into_iter()
+        .map(|y| { let (vx, vy) = view.to_view(view.min_x, y); (vx, vy, y) })
+        .collect();
+
+    // after a quarter turn the level's Y axis runs across the top
+    let (top, left) = match turns % 2 {
+        0 => (xs, ys),
+        _ => (ys, xs),
+    };
+
+    // labels are centered over their tick along the top, and end just
+    // short of it down the left side
+    let edge = view.padding as f64;
+    let tick = font / 3.0;
+    for (vx, _, v) in top {
+        let text = format!("{}", v);
+        layer.add_object(Box::new(
+            SVGLine::shape(vx, edge - tick, vx, edge).with_class("axis"),
+        ));
+        layer.add_object(Box::new(
+            SVGText::shape(vx, edge - font / 2.0, font, &text)
+                .with_class("axis")
+                .with_attr("text-anchor", "middle"),
+        ));
+    }
+    for (_, vy, v) in left {
+        let text = format!("{}", v);
+        layer.add_object(Box::new(
+            SVGLine::shape(edge - tick, vy, edge, vy).with_class("axis"),
+        ));
+        layer.add_object(Box::new(
+            SVGText::shape(edge - font / 2.0, vy + tick, font, &text)
+                .with_class("axis")
+                .with_attr("text-anchor", "end"),
+        ));
+    }
+}
+
+
+// room left around the things when cropping the map tightly to them
+const THING_MARGIN: i32 = 64;
+
+
+fn line_bounds(lev: &Level) -> (i32, i32, i32, i32) {
+    match lev.bounds() {
+        Some((x1, y1, x2, y2)) => (x1 as i32, y1 as i32, x2 as i32, y2 as i32),
+        None                   => (0, 0, 0, 0),
+    }
+}
+
+
+// clip a linedef to the map and place it on the viewbox
+fn line_points(lev: &Level, view: &Projection, linedef: &LineDef) -> Option<(f64, f64, f64, f64)> {
+    let a = lev.vertices.get(linedef.start)?;
+    let b = lev.vertices.get(linedef.end)?;
+    let (a, b) = view.clip_line((a.x as i32, a.y as i32), (b.x as i32, b.y as i32))?;
+    let (x1, y1) = view.to_view(a.0, a.1);
+    let (x2, y2) = view.to_view(b.0, b.1);
+    Some((x1, y1, x2, y2))
+}
+
+
+// convert a &Level into an SVG Buffer
+// calculates a lot of numbers and converts LineDefs into SVGLine objects
+pub fn level_to_svg(lev: &Level, wad: &Wad, opts: &Options) -> SVG {
+    // the map spans every vertex a linedef uses, just the things (plus a
+    // little room around them) when cropping tightly to them, or whatever
+    // region was asked for
+    let (min_x, min_y, max_x, max_y) = match (opts.crop, opts.crop_things) {
+        (Some(region), _) => region,
+        (None, true) => match lev.thing_bounds() {
+            Some((x1, y1, x2, y2)) => (
+                x1 as i32 - THING_MARGIN, y1 as i32 - THING_MARGIN,
+                x2 as i32 + THING_MARGIN, y2 as i32 + THING_MARGIN,
+            ),
+            None => line_bounds(lev),
+        },
+        _ => line_bounds(lev),
+    };
+
+    // padding from the edge of the image, widened to fit the axis labels
+    let mut view = Projection::new(min_x, min_y, max_x, max_y, opts.padding)
+        .with_rotation(opts.rotate)
+        .with_mirror(opts.mirror);
+    let font_size = f64::max(1.0, max(view.width, view.height) as f64 / 50.0);
+    if opts.axes {
+        view.padding = max(view.padding, (font_size * 4.0).ceil() as u64);
+    }
+    let padding = view.padding;
 
     // viewbox numbers that include the padding for the image
-    let vx = mx + (2 * padding as i32);
-    let vy = my + (2 * padding as i32);
+    let vx = view.view_width() as i32;
+    let map_vy = view.view_height() as i32;
+
+    // work out how every line is drawn before making the canvas, since
+    // the legend (and with it the height of the viewbox) depends on it
+    // lines that fall entirely outside the map are left out
+    let points: Vec<Option<(f64, f64, f64, f64)>> = lev.linedefs
+        .iter()
+        .map(|linedef| line_points(lev, &view, linedef))
+        .collect();
+
+    let mut looks: Vec<Option<LineLook>> = Vec::new();
+    let mut classes: Vec<(usize, String)> = Vec::new(); // layer and CSS classes of each drawn line
+    let mut secret_labels: Vec<(f64, f64, &str)> = Vec::new();
+    for (linedef, pts) in lev.linedefs.iter().zip(points.iter()) {
+        let (x1, y1, x2, y2) = match *pts {
+            Some(pts) => pts,
+            None      => { looks.push(None); continue; }
+        };
+
+        // one-sided, two-sided and special lines use differentiating colors and widths
+        let mut look = match automap_style(linedef, wad, opts) {
+            Some(look) => look,
+            None       => { looks.push(None); continue; }
+        };
+
+        let class = classify(linedef, wad.is_hexen, wad.game);
+        let mut css = line_classes(linedef, class);
+
+        // secret sectors are outlined, and the ways into them and secret
+        // exits get a label at their midpoint
+        if opts.secrets {
+            let role = secret_role(linedef, lev, wad);
+            match role {
+                SecretRole::Entrance => { css.push_str(" secret-entrance"); }
+                SecretRole::Border   => { css.push_str(" secret-border"); }
+                SecretRole::Outside  => {}
+            }
+
+            let text = match class {
+                LineClass::SecretExit => {
+                    look.width = 9.0;
+                    look.label = String::from("Secret exit");
+                    Some("Secret exit")
+                }
+                _ if role == SecretRole::Outside => None,
+                _ => {
+                    look.label = String::from("Secret area");
+                    match role {
+                        SecretRole::Entrance => Some("S"),
+                        _                    => Some(""),
+                    }
+                }
+            };
+
+            if let Some(text) = text {
+                look.color = Color::Magenta;
+                if !text.is_empty() {
+                    secret_labels.push(((x1 + x2) / 2.0, (y1 + y2) / 2.0, text));
+                }
+            }
+        }
+
+        classes.push((line_layer(linedef, class), css));
+        looks.push(Some(look));
+    }
+
+    let k = stroke_factor(opts);
+    for look in looks.iter_mut().flatten() {
+        look.width = scaled(look.width, k);
+    }
+
+    // every way a line is drawn gets a class named after its legend label,
+    // numbered when one label is drawn more than one way
+    let mut named: Vec<LineLook> = Vec::new();
+    for (look, (_, css)) in looks.iter_mut().flatten().zip(classes.iter_mut()) {
+        let same = named.iter().find(|n| {
+            n.label == look.label && n.color == look.color && n.width == look.width && n.dashed == look.dashed
+        });
+        look.class = match same {
+            Some(n) => n.class.clone(),
+            None    => {
+                let base = format!("look-{}", css_name(&look.label));
+                let class = match named.iter().filter(|n| n.label == look.label).count() {
+                    0 => base,
+                    n => format!("{}-{}", base, n + 1),
+                };
+                named.push(LineLook{ class: class.clone(), ..look.clone() });
+                class
+            }
+        };
+        css.push(' ');
+        css.push_str(&look.class);
+    }
+    let dash = scaled(20.0, k);
+
+    // the legend sits in its own strip below the map so it never covers
+    // any lines, growing the viewbox by however tall it turns out
+    let legend = build_legend(lev, wad, opts, &looks);
+    let legend_width = (vx as u64 - 2 * padding) as f64;
+    let vy = map_vy + legend.height(legend_width, font_size).ceil() as i32;
 
     // calculate the image canvas size by using the aspect ratio of the viewbox numbers
+    // or straight from the viewbox when a fixed scale is given
     let base_canvas_size: f64 = opts.target_size as f64;
     let cx : u64;
     let cy : u64;
-    if vx > vy {
+    if let Some(scale) = opts.scale {
+        cx = max(1, (vx as f64 * scale).round() as u64);
+        cy = max(1, (vy as f64 * scale).round() as u64);
+    } else if vx > vy {
         let ratio = base_canvas_size / vx as f64;
         cx = (vx as f64 * ratio) as u64;
         cy = (vy as f64 * ratio) as u64;
@@ -128,75 +768,335 @@ fn level_to_svg(lev: &Level, opts: &Options) -> SVG {
     }
 
     let mut buf = SVG::new(cx, cy, vx as u64, vy as u64);
+    buf.set_style(&map_style(&named, opts, dash));
+
+    // everything is drawn into named layers so they can be restyled or
+    // hidden separately once the map is opened in an editor; things have
+    // a layer of their own for an editor to add them to
+    let mut layers: Vec<SVGGroup> = LAYER_NAMES.iter().map(|name| SVGGroup::new(name)).collect();
 
     // check if we want a transparent background
     // if not, add a white background matching the dimensions
     if !opts.transparent {
-        buf.add_object(Box::new(SVGRect::new(
-            0,
-            0,
-            vx as u64,
-            vy as u64,
-            match opts.inverted {
-                true => Color::Black,
-                _    => Color::White,
+        layers[LAYER_BACKGROUND].add_object(Box::new(
+            SVGRect::new(0.0, 0.0, vx as f64, vy as f64, Color::None)
+                .styled()
+                .with_class("background"),
+        ));
+    }
+
+    let fg = match opts.inverted {
+        true => Color::White,
+        _    => Color::Black,
+    };
+
+    // the grid and coordinate labels go underneath everything else
+    if opts.grid > 0 {
+        add_grid(&mut buf, &mut layers[LAYER_GRID], &view, opts.grid, scaled(2.0, k), opts.inverted);
+    }
+    if opts.axes {
+        add_axes(&mut buf, &mut layers[LAYER_GRID], &view, opts.grid, font_size, &fg, k);
+    }
+
+    // hazards are drawn first so every line sits on top of them
+    if opts.hazards {
+        add_hazards(&mut buf, &mut layers[LAYER_SECTORS], lev, wad, &view, k);
+    }
+
+    let drawn = points.iter().zip(looks).filter_map(|(pts, look)| match (*pts, look) {
+        (Some(pts), Some(look)) => Some((pts, look)),
+        _                       => None,
+    });
+    if opts.optimize {
+        // a run of lines in a layer with the same look and classes becomes
+        // one path; only runs are merged, so lines still paint in order
+        let mut merged: Vec<(usize, String, LineLook, Vec<optimize::Segment>)> = Vec::new();
+        for ((pts, look), (layer, css)) in drawn.zip(classes) {
+            let found = merged.iter().rposition(|m| m.0 == layer).filter(|&i| merged[i].1 == css);
+            match found {
+                Some(i) => merged[i].3.push(pts),
+                None    => merged.push((layer, css, look, vec![pts])),
             }
-        )));
-    }
-
-    for linedef in &lev.linedefs {
-        let a = &lev.vertices[linedef.start];
-        let b = &lev.vertices[linedef.end];
-
-        let ax = ((a.x as i32) + shift_x) as u64;
-        let ay = ((a.y as i32) + shift_y) as u64;
-        let bx = ((b.x as i32) + shift_x) as u64;
-        let by = ((b.y as i32) + shift_y) as u64;
-
-        buf.add_object(Box::new(SVGLine::new(
-            padding + flatten(ax, 0),
-            padding + flatten(ay, my as u64),
-            padding + flatten(bx, 0),
-            padding + flatten(by, my as u64),
-            // if a linedef is one-sided use differentiating colors and widths
-            match linedef.is_one_sided() {
-                true => 7,
-                _ => 5,
-            },
+        }
+
+        for (layer, css, look, segs) in merged {
+            layers[layer].add_object(Box::new(
+                SVGPath::new(Color::None)
+                    .with_data(&optimize::path_data(&segs, !look.dashed))
+                    .styled()
+                    .with_class(&css),
+            ));
+        }
+    } else {
+        for (((x1, y1, x2, y2), _), (layer, css)) in drawn.zip(classes) {
+            layers[layer].add_object(Box::new(SVGLine::shape(x1, y1, x2, y2).with_class(&css)));
+        }
+    }
+
+    // labels go on top of every line so nothing covers them
+    for &(x, y, text) in &secret_labels {
+        layers[LAYER_LABELS].add_object(Box::new(
+            SVGText::shape(x, y, font_size, text)
+                .with_class("label secret-label")
+                .with_attr("text-anchor", "middle"),
+        ));
+    }
+
+    if !legend.is_empty() {
+        buf.add_style(&legend.style(&fg, k));
+    }
+    legend.draw(&mut layers[LAYER_LEGEND], (padding as f64, map_vy as f64), legend_width, font_size);
 
-            line_color(linedef, opts.color_doors, opts.inverted)
-        )));
+    // every layer is written, even an empty one, so a stylesheet or an
+    // editor can count on finding all of them
+    for layer in layers {
+        buf.add_object(Box::new(layer));
     }
+
     return buf;
 }
 
 
+// a hash of a level's lumps, its flat colors and the options it's drawn with
+fn render_hash(lev: &Level, wad: &Wad, opts: &Options) -> String {
+    let mut flats: Vec<&String> = lev.sectors.iter().map(|s| &s.floor_tex).collect();
+    flats.sort();
+    flats.dedup();
+    let colors: Vec<Option<&(u8, u8, u8)>> = flats.iter().map(|f| wad.flats.get(*f)).collect();
+
+    // the legend shows the WAD's file name, but not the path it was found by
+    let drawn = format!(
+        "{:?} {:?} {:?} {}",
+        wad_file_name(wad), wad.titles.get(&lev.name), wad.game, wad.is_hexen,
+    );
+
+    let mut hasher = Hasher::new();
+    hasher
+        .piece(env!("CARGO_PKG_VERSION").as_bytes())
+        .piece(&lev.hash.to_le_bytes())
+        .piece(format!("{:?} {:?}", flats, colors).as_bytes())
+        .piece(drawn.as_bytes())
+        .piece(opts.render_key().as_bytes());
+    to_hex(hasher.finish())
+}
+
+
+// what became of one level
+struct Made {
+    path:    PathBuf,
+    skipped: bool,           // the file was left as it was
+    hash:    Option<String>, // the file was written from this
+}
+
+
 // Take a &Wad and start converting all it's levels to SVG buffers
 // Using said buffers, write each one to a corresponding file
-pub fn make_maps_from_wad(fname: &str, wad: &Wad, opts: &Options) -> Result<u8, String> {
-    let wad_dir_name = dir_name(fname);
-    let dir_made = make_directory(&wad_dir_name);
+// and note every level written (or skipped) in the file's report
+pub fn make_maps_from_wad(fname: &str, wad: &Wad, opts: &Options, report: &mut FileReport) -> Result<u8, String> {
+    let levels = select_levels(&wad.levels, &opts.maps, fname);
+    make_maps(fname, wad, &levels, opts, report)
+}
 
-    if dir_made && opts.verbose {
-        println!("Directory made!");
-    }
 
-    for lev in &wad.levels {
-        let mut svg_thing = level_to_svg(&lev, opts);
-        let output_path = make_path_str(&wad_dir_name, &lev.name);
+// render the given levels of a Wad
+pub fn make_maps(fname: &str, wad: &Wad, levels: &[&Level], opts: &Options, report: &mut FileReport) -> Result<u8, String> {
+    if opts.to_stdout() && levels.len() > 1 {
+        return Err(String::from("Err: --output - writes a single map, pick one with --map"));
+    }
 
-        match svg_thing.to_file(&output_path) {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(String::from(format!("Error: {}", e)));
+    // levels are rendered --jobs at a time, and reported in order
+    let mut failed = None;
+    let mut written = Vec::new();
+    pool::run(opts.jobs, levels, |lev| make_map(fname, lev, wad, opts), |lev, made| {
+        match made {
+            Ok(made) => {
+                report.add_level(&lev.name, Some(made.path.display().to_string()), made.skipped);
+                if let Some(hash) = made.hash {
+                    written.push((made.path, hash));
+                }
             }
+            Err(why) => { failed = failed.take().or(Some(why)); }
         }
+    });
+
+    // remember what was drawn, even if some levels went wrong
+    cache::record(&written)?;
+    if let Some(why) = failed {
+        return Err(why);
     }
 
     if opts.verbose {
-        println!("Finished rendering maps for {}", fname);
+        say!("Finished rendering maps for {}", fname);
     }
     return Ok(0);
 }
 
+
+// render one level to its file, unless the file is already there and was
+// drawn from the same level and options
+fn make_map(fname: &str, lev: &Level, wad: &Wad, opts: &Options) -> Result<Made, String> {
+    // a map sent to stdout isn't a file, so there's nothing to cache or keep
+    if opts.to_stdout() {
+        level_to_svg(lev, wad, opts).to_stdout()?;
+        return Ok(Made{ path: PathBuf::from("-"), skipped: false, hash: None });
+    }
+
+    let path = output_path(fname, &lev.name, "svg", opts)?;
+    let hash = render_hash(lev, wad, opts);
+    if !opts.force && cache::is_fresh(&path, &hash) {
+        if opts.verbose {
+            say!("Skipped {}, it hasn't changed", path.display());
+        }
+        return Ok(Made{ path: path, skipped: true, hash: None });
+    }
+
+    if !prepare(&path, opts.existing)? {
+        if opts.verbose {
+            say!("Skipped {}, it already exists", path.display());
+        }
+        return Ok(Made{ path: path, skipped: true, hash: None });
+    }
+
+    let svg_thing = level_to_svg(lev, wad, opts);
+    svg_thing.to_file(&path)?;
+    if opts.verbose {
+        say!("Wrote {}", path.display());
+    }
+    Ok(Made{ path: path, skipped: false, hash: Some(hash) })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use mapmaker::*;
+    use std::collections::HashMap;
+    use doom::game::Game;
+    use doom::sector::Sector;
+    use doom::sidedef::SideDef;
+    use doom::vertex::Vertex;
+
+    fn sector(stype: u16) -> Sector {
+        Sector{
+            ceil: 128, stag: 0, floor: 0, light: 160, stype: stype,
+            ceil_tex: String::from("CEIL"), floor_tex: String::from("FLOOR"),
+        }
+    }
+
+    fn side(sector: u16) -> SideDef {
+        SideDef{
+            x_offset: 0, y_offset: 0, sector: sector,
+            upper_tex: String::from("-"), lower_tex: String::from("-"), middle_tex: String::from("-"),
+        }
+    }
+
+    fn line(right: i16, left: i16, flags: u16, stype: u16) -> LineDef {
+        LineDef{ start: 0, end: 1, left: left, right: right, tag: 0, flags: flags, stype: stype, args: [0; 6] }
+    }
+
+    #[test]
+    fn test_secret_roles() {
+        let wad = Wad{
+            name:     String::from("test.wad"),
+            header:   WadHeader{ wadtype: 0, numlumps: 0, lumpaddr: 0 },
+            levels:   Vec::new(),
+            is_hexen: false,
+            game:     Game::Doom,
+            flats:    HashMap::new(),
+            titles:   HashMap::new(),
+        };
+        // side 0 faces an ordinary sector, side 1 a secret one
+        let lev = Level{
+            name:     String::from("MAP01"),
+            things:   Vec::new(),
+            vertices: Vec::new(),
+            linedefs: Vec::new(),
+            sidedefs: vec![side(0), side(1), side(0)],
+            sectors:  vec![sector(0), sector(9)],
+            hash:     0,
+        };
+
+        // a plain wall flagged to hide it on the automap leads nowhere
+        assert_eq!(secret_role(&line(0, -1, 0x20, 0), &lev, &wad), SecretRole::Outside);
+        // but a flagged door does, and so does a flagged wall of a secret
+        assert_eq!(secret_role(&line(0, 2, 0x20, 1), &lev, &wad), SecretRole::Entrance);
+        assert_eq!(secret_role(&line(1, -1, 0x20, 0), &lev, &wad), SecretRole::Entrance);
+        assert_eq!(secret_role(&line(1, -1, 0, 0), &lev, &wad), SecretRole::Border);
+        assert_eq!(secret_role(&line(0, 1, 0, 0), &lev, &wad), SecretRole::Entrance);
+    }
+
+    // the classes of every line in a layer, in the order they are painted
+    fn painted(svg: &str, layer: &str) -> Vec<String> {
+        let start = svg.find(&format!("<g id=\"{}\"", layer)).unwrap();
+        let group = &svg[start..start + svg[start..].find("</g>").unwrap()];
+        group.split("class=\"").skip(1).map(|s| s[..s.find('"').unwrap()].to_owned()).collect()
+    }
+
+    // a zigzag of one-sided lines, each with the given special
+    fn zigzag(specials: &[u16]) -> Level {
+        Level{
+            name:     String::from("MAP01"),
+            things:   Vec::new(),
+            vertices: (0..=specials.len() as i16).map(|i| Vertex{ x: i * 64, y: (i % 2) * 64 }).collect(),
+            linedefs: specials.iter().enumerate().map(|(i, &stype)| LineDef{
+                start: i, end: i + 1, ..line(0, -1, 0, stype)
+            }).collect(),
+            sidedefs: vec![side(0)],
+            sectors:  vec![sector(0)],
+            hash:     0,
+        }
+    }
+
+    #[test]
+    fn test_lines_are_painted_by_the_stylesheet() {
+        let wad = Wad{
+            name:     String::from("test.wad"),
+            header:   WadHeader{ wadtype: 0, numlumps: 0, lumpaddr: 0 },
+            levels:   Vec::new(),
+            is_hexen: false,
+            game:     Game::Doom,
+            flats:    HashMap::new(),
+            titles:   HashMap::new(),
+        };
+        let opts = Options{ specials: true, ..Options::default() };
+        let svg = String::from_utf8(level_to_svg(&zigzag(&[0, 1, 0]), &wad, &opts).to_bytes()).unwrap();
+
+        assert!(svg.contains(".look-wall { stroke: black; stroke-width: 7; }"));
+        assert!(svg.contains(".look-door { stroke: brown; stroke-width: 7; }"));
+        assert_eq!(painted(&svg, "walls"), vec!["line one-sided look-wall"; 2]);
+        assert!(!svg.contains("stroke=\""));
+
+        // every layer is there, even with nothing in it
+        for name in LAYER_NAMES.iter() {
+            assert!(svg.contains(&format!("<g id=\"{}\">", name)));
+        }
+    }
+
+    #[test]
+    fn test_optimize_keeps_paint_order() {
+        let wad = Wad{
+            name:     String::from("test.wad"),
+            header:   WadHeader{ wadtype: 0, numlumps: 0, lumpaddr: 0 },
+            levels:   Vec::new(),
+            is_hexen: false,
+            game:     Game::Doom,
+            flats:    HashMap::new(),
+            titles:   HashMap::new(),
+        };
+        // a door, a lift, a second door joined to the first and a lift
+        // again, so merging by look alone would move the second door down
+        let lev = zigzag(&[1, 62, 1, 1, 62]);
+
+        let mut opts = Options::default();
+        opts.specials = true;
+        let lines = painted(&String::from_utf8(level_to_svg(&lev, &wad, &opts).to_bytes()).unwrap(), "special-lines");
+        opts.optimize = true;
+        let paths = painted(&String::from_utf8(level_to_svg(&lev, &wad, &opts).to_bytes()).unwrap(), "special-lines");
+
+        let mut runs = lines.clone();
+        runs.dedup();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(paths, runs);
+    }
+}
+
 // end
@@ -0,0 +1,225 @@
+// optimize.rs
+
+/// Turns a pile of line segments that share one style into the data for a
+/// single <path>, which is far smaller than a <line> per segment:
+///
+///   1. segments carrying straight on from one another are chained
+///   2. points in the middle of a straight run are dropped
+///   3. the path is written with relative, compact coordinates
+///
+/// Segments are only chained where they carry straight on, so every
+/// corner still starts a new subpath and is drawn with the same two butt
+/// ends as the separate lines it replaces. Dashed lines are never
+/// chained, since a dash pattern restarts at every subpath.
+
+use std::collections::HashMap;
+
+
+// points are kept in hundredths of a viewbox unit, the precision the
+// svg module writes coordinates at, so matching ends compare exactly
+type Point = (i64, i64);
+
+
+/// A line segment from (x1, y1) to (x2, y2) in viewbox units
+pub type Segment = (f64, f64, f64, f64);
+
+
+fn to_point(x: f64, y: f64) -> Point {
+    ((x * 100.0).round() as i64, (y * 100.0).round() as i64)
+}
+
+
+// whether going from a through b on to c carries straight on
+fn straight_on(a: Point, b: Point, c: Point) -> bool {
+    let (dx1, dy1) = (b.0 - a.0, b.1 - a.1);
+    let (dx2, dy2) = (c.0 - b.0, c.1 - b.1);
+    dx1 * dy2 - dy1 * dx2 == 0 && dx1 * dx2 + dy1 * dy2 > 0
+}
+
+
+// chain segments into straight runs by walking from each unused segment
+// through any unused segment that carries on from its end, both ways
+fn chain(segs: &[(Point, Point)]) -> Vec<Vec<Point>> {
+    let mut ends: HashMap<Point, Vec<usize>> = HashMap::new();
+    for (i, &(a, b)) in segs.iter().enumerate() {
+        ends.entry(a).or_default().push(i);
+        ends.entry(b).or_default().push(i);
+    }
+
+    let mut used = vec![false; segs.len()];
+    let mut lines: Vec<Vec<Point>> = Vec::new();
+    for first in 0..segs.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+
+        let mut line = vec![segs[first].0, segs[first].1];
+        for forwards in &[true, false] {
+            loop {
+                let (from, at) = match *forwards {
+                    true => (line[line.len() - 2], line[line.len() - 1]),
+                    _    => (line[1], line[0]),
+                };
+                let other_end = |s: usize| match segs[s].0 == at {
+                    true => segs[s].1,
+                    _    => segs[s].0,
+                };
+                let next = ends
+                    .get(&at)
+                    .and_then(|list| list.iter().cloned().find(|&s| !used[s] && straight_on(from, at, other_end(s))));
+                let other = match next {
+                    Some(s) => { used[s] = true; other_end(s) }
+                    None    => { break; }
+                };
+                match *forwards {
+                    true => line.push(other),
+                    _    => line.insert(0, other),
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+
+// drop every point that sits on a straight run between its neighbours
+fn merge_collinear(line: &[Point]) -> Vec<Point> {
+    let mut out: Vec<Point> = Vec::with_capacity(line.len());
+    for &p in line {
+        if out.len() >= 2 && straight_on(out[out.len() - 2], out[out.len() - 1], p) {
+            out.pop();
+        }
+        out.push(p);
+    }
+    out
+}
+
+
+// write a value given in hundredths as briefly as possible
+fn compact(v: i64) -> String {
+    let sign = match v < 0 { true => "-", _ => "" };
+    let (whole, frac) = (v.abs() / 100, v.abs() % 100);
+    match (whole, frac) {
+        (w, 0)                => format!("{}{}", sign, w),
+        (0, f) if f % 10 == 0 => format!("{}.{}", sign, f / 10),
+        (0, f)                => format!("{}.{:02}", sign, f),
+        (w, f) if f % 10 == 0 => format!("{}{}.{}", sign, w, f / 10),
+        (w, f)                => format!("{}{}.{:02}", sign, w, f),
+    }
+}
+
+
+// writes path commands, leaving out every separator and repeated
+// command letter the path grammar lets us skip
+struct PathWriter {
+    d:    String,
+    last: char,
+}
+
+
+impl PathWriter {
+    fn command(&mut self, cmd: char, args: &[i64]) {
+        if cmd != self.last {
+            self.d.push(cmd);
+            self.last = cmd;
+        } else if !args.is_empty() {
+            self.separate(args[0]);
+        }
+        for (i, &v) in args.iter().enumerate() {
+            if i > 0 {
+                self.separate(v);
+            }
+            self.d.push_str(&compact(v));
+        }
+    }
+
+    // numbers only need a space between them when the next has no sign
+    fn separate(&mut self, next: i64) {
+        if next >= 0 {
+            self.d.push(' ');
+        }
+    }
+}
+
+
+/// Path data drawing every segment
+/// Pass chained as false to keep every segment its own subpath
+pub fn path_data(segs: &[Segment], chained: bool) -> String {
+    let segs: Vec<(Point, Point)> = segs
+        .iter()
+        .map(|&(x1, y1, x2, y2)| (to_point(x1, y1), to_point(x2, y2)))
+        .filter(|&(a, b)| a != b)
+        .collect();
+
+    let lines: Vec<Vec<Point>> = match chained {
+        true => chain(&segs).iter().map(|line| merge_collinear(line)).collect(),
+        _    => segs.iter().map(|&(a, b)| vec![a, b]).collect(),
+    };
+
+    let mut w = PathWriter{ d: String::new(), last: ' ' };
+    let mut at: Point = (0, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let start = line[0];
+        match i {
+            0 => w.command('M', &[start.0, start.1]),
+            _ => w.command('m', &[start.0 - at.0, start.1 - at.1]),
+        }
+        at = start;
+
+        for &p in &line[1..] {
+            match (p.0 - at.0, p.1 - at.1) {
+                (dx, 0) => w.command('h', &[dx]),
+                (0, dy) => w.command('v', &[dy]),
+                (dx, dy) => w.command('l', &[dx, dy]),
+            }
+            at = p;
+        }
+    }
+    w.d
+}
+
+
+#[cfg(test)]
+mod tests {
+    use optimize::*;
+
+    #[test]
+    fn test_compact_numbers() {
+        assert_eq!(compact(1200), "12");
+        assert_eq!(compact(-50), "-.5");
+        assert_eq!(compact(1205), "12.05");
+        assert_eq!(compact(-1230), "-12.3");
+    }
+
+    #[test]
+    fn test_straight_runs_are_chained() {
+        // the bottom side of a square split in three, given out of order
+        // and in mixed directions, becomes one straight line
+        let segs = vec![(4.0, 0.0, 10.0, 0.0), (2.0, 0.0, 0.0, 0.0), (4.0, 0.0, 2.0, 0.0)];
+        assert_eq!(path_data(&segs, true), "M0 0h10");
+    }
+
+    #[test]
+    fn test_corners_are_not_chained() {
+        // the sides of a square meet at corners, so chaining has to leave
+        // them as the same subpaths the separate lines would draw
+        let segs = vec![
+            (0.0, 0.0, 10.0, 0.0),
+            (10.0, 0.0, 10.0, 10.0),
+            (10.0, 10.0, 0.0, 10.0),
+            (0.0, 10.0, 0.0, 0.0),
+        ];
+        assert_eq!(path_data(&segs, true), path_data(&segs, false));
+        assert_eq!(path_data(&segs, true), "M0 0h10m0 0v10m0 0h-10m0 0v-10");
+    }
+
+    #[test]
+    fn test_unchained_segments() {
+        let segs = vec![(0.0, 0.0, 3.0, 4.0), (3.0, 4.0, 3.5, 1.0)];
+        assert_eq!(path_data(&segs, false), "M0 0l3 4m0 0l.5-3");
+    }
+}
+
+// end
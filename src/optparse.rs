@@ -1,42 +1,478 @@
 // optparse.rs
 
+use std::cmp::{min, max};
 use std::env::args;
+use std::str::FromStr;
+use doom::game::Game;
+use mapfilter::MapFilter;
+use output::{DEFAULT_TEMPLATE, check_template};
 
 /// This is where command line options are parsed
 /// Turn the CLI options into a Struct for pass-through to various functions
 
-const HELP_STR: &'static str = "Usage: wad2map [OPTION] ... [FILE] ...
+const HELP_STR: &'static str = "Usage: wad2map [COMMAND] [OPTION] ... [FILE] ...
 Convert all levels from a list of WADs into SVG files
-exported to matching directories of the original WAD filepath
+exported to matching directories of the original WAD filepath,
+or look inside the WADs with one of the other commands
 
+Commands:
+  render   Render every level to an SVG file (the default)
+  info     Show the WAD header and the game it is for
+  list     List the lumps and levels in the WAD
+  extract  Write lumps out to files
+  check    Look for damage in the WAD and its levels
+  stats    Count the things, lines and sectors of every level
+  export   Write the data of every level out as JSON
 
-  -h, --help         Show this help and exit
+Options for every command:
+  -h, --help         Show this help (or a command's help) and exit
   -v, --version      Show program version and exit
   -V, --verbose      Toggle program verbosity
+  -g, --game [NAME]  Treat WADs as doom, heretic, hexen or strife
+                     (guessed from the WAD's lumps by default)
+      --map [NAME]   Only render, list or count levels matching NAME, which
+                     can be a name (MAP07), a glob (E2M*) or a range
+                     (MAP01-MAP10); repeat it or use commas for more
+      --report json  Print a JSON summary of every file at the end, with
+                     its levels, where they were written and any errors
+  -j, --jobs [N]     Work on N files, or the levels of a file, at once
+                     (default 1); output still comes in the order given
+      --include [GLOB]
+      --exclude [GLOB]
+                     Only work on, or skip, the files found in directories
+                     that match GLOB (by name, or by path under the
+                     directory if GLOB has a '/'); repeat them for more
+
+A directory is searched, along with every directory inside it, for files
+ending in .wad, .pk3 or .zip (zip archives can't be read yet, so each
+one found is counted as a failed file)
+
+Values can be given as '--size 512', '--size=512' or '-s512', short
+options can be bundled together ('-tdi'), and everything after '--'
+is taken to be a file
+
+Run 'wad2map help COMMAND' to see the options of a command
+
+Examples:
+  wad2map doom.wad        Exports all levels into './doom.wad.maps'
+  wad2map -t heretic.wad  Exports all Heretic levels as transparent
+  wad2map list doom.wad   Lists every lump and level in doom.wad
+
+More help can be found at <https://github.com/sleibrock/wad2map>
+";
+
+
+const RENDER_HELP: &'static str = "Usage: wad2map [render] [OPTION] ... [FILE] ...
+Convert all levels from a list of WADs into SVG files
+exported to matching directories of the original WAD filepath
+
   -t, --transparent  Render images with no backgrounds
   -l, --lighting     Render images using sector lighting
   -i, --invert       Invert the colors (black bg, white fg)
   -s, --size [NUM]   Change the base canvas size
+      --scale [NUM]  Render at a fixed scale instead, in pixels per map unit
+                     (0.25 draws 4 map units to a pixel on every map)
   -d, --doors        Color all keycard/skullkey doors
+  -c, --specials     Color special lines (doors, lifts, exits, teleporters...)
+  -a, --automap      Draw lines the way the in-game automap does
+                     (secret lines look like walls, hidden lines are skipped)
+      --automap-start
+                     Like --automap, but only lines mapped at level start
+                     are in full color, the rest as a computer area map
+      --spoiler      Like --automap, but draw hidden and secret lines dashed
+  -S, --secrets      Outline secret sectors and mark secret exits and
+                     the lines leading into secret areas
+  -z, --hazards      Fill liquid floors and hatch floors that hurt
+  -L, --legend       Add a title block, scale bar and color key under each map
+      --crop-things  Crop the map tightly around its things, leaving out
+                     scenery with nothing placed in it
+      --crop [X1,Y1,X2,Y2]
+                     Only render the region between two corners,
+                     given in map coordinates
+  -r, --rotate [DEG] Rotate the map counter-clockwise by DEG degrees
+  -m, --mirror       Mirror the map left to right
+      --padding [NUM]
+                     Space left around the map, in map units (default 50)
+  -G, --grid [NUM]   Draw a grid every NUM map units under the map
+                     (128 matches the blockmap, 64 a typical editor grid)
+  -x, --axes         Label the edges of the map with level coordinates
+  -O, --optimize     Merge lines of the same style into single paths for
+                     much smaller files (lines can't be edited one by one)
+  -o, --output [DIR] Write files under DIR instead of next to each WAD, or
+                     write the one map asked for to stdout with '-'
+      --name [TEMPLATE]
+                     Name files after a template, relative to the output
+                     directory, using {file} (doom2.wad), {wad} (doom2),
+                     {map}, {mode} (full, automap, start or spoiler)
+                     and {ext} (default '{file}.maps/{map}.{ext}')
+      --if-exists [POLICY]
+                     What to do with files that are already there:
+                     overwrite (the default), skip or fail
+      --mirror-tree  With --output, keep the directories files were found
+                     in under the output directory
+  -f, --force        Render every level, even ones that haven't changed
+                     since they were last rendered with the same options
+  -w, --watch        Keep running, and render levels again whenever their
+                     WAD is saved with changes to them
+";
 
-Examples:
-  wad2map doom.wad        Exports all levels into './doom.wad.maps'
-  wad2map -t heretic.wad  Exports all Heretic levels as transparent
 
-More help can be found at <https://github.com/sleibrock/wad2map>
+const INFO_HELP: &'static str = "Usage: wad2map info [OPTION] ... [FILE] ...
+Show the header of each WAD, the game it is for and how many levels it has
+
+With --verbose the size of every level is listed as well
+";
+
+
+const LIST_HELP: &'static str = "Usage: wad2map list [OPTION] ... [FILE] ...
+List the lumps and levels in each WAD
+
+      --lumps        Only list the lumps, with their offsets and sizes
+      --levels       Only list the levels, with their titles
+";
+
+
+const EXTRACT_HELP: &'static str = "Usage: wad2map extract [OPTION] ... [FILE] ...
+Write the lumps of each WAD out to '.lmp' files in a matching directory,
+with the lumps of every level in a directory of their own
+
+      --lump [NAME]  Only extract lumps with this name (can be repeated)
+      --dir [DIR]    Write the lumps into DIR instead of './FILE.lumps'
+";
+
+
+const CHECK_HELP: &'static str = "Usage: wad2map check [OPTION] ... [FILE] ...
+Look for damage in each WAD: lumps pointing outside of the file, levels
+missing lumps, and lines or sides referring to things that don't exist
+";
+
+
+const STATS_HELP: &'static str = "Usage: wad2map stats [OPTION] ... [FILE] ...
+Count the things, lines, sides, sectors and secrets of every level
+and measure how large each one is
 ";
 
 
+const EXPORT_HELP: &'static str = "Usage: wad2map export [OPTION] ... [FILE] ...
+Write everything parsed from every level (vertices, linedefs with their
+flags and specials decoded, sidedefs, sectors, things and a little about
+the level) to a JSON file, named the same way rendered maps are
+
+      --json         Write JSON (the default, and so far the only format)
+      --compact      Write each file on one line instead of indented
+  -o, --output [DIR] Write files under DIR instead of next to each WAD, or
+                     write the one level asked for to stdout with '-'
+      --name [TEMPLATE]
+                     Name files after a template, see 'wad2map help render'
+                     (default '{file}.maps/{map}.{ext}', where {ext} is json)
+      --if-exists [POLICY]
+                     What to do with files that are already there:
+                     overwrite (the default), skip or fail
+      --mirror-tree  With --output, keep the directories files were found
+                     in under the output directory
+";
+
+
+/// What the program has been asked to do with each WAD
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Render,
+    Info,
+    List,
+    Extract,
+    Check,
+    Stats,
+    Export,
+}
+
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "render"  => Some(Command::Render),
+            "info"    => Some(Command::Info),
+            "list"    => Some(Command::List),
+            "extract" => Some(Command::Extract),
+            "check"   => Some(Command::Check),
+            "stats"   => Some(Command::Stats),
+            "export"  => Some(Command::Export),
+            _         => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Command::Render  => "render",
+            Command::Info    => "info",
+            Command::List    => "list",
+            Command::Extract => "extract",
+            Command::Check   => "check",
+            Command::Stats   => "stats",
+            Command::Export  => "export",
+        }
+    }
+
+    fn help(&self) -> &'static str {
+        match *self {
+            Command::Render  => RENDER_HELP,
+            Command::Info    => INFO_HELP,
+            Command::List    => LIST_HELP,
+            Command::Extract => EXTRACT_HELP,
+            Command::Check   => CHECK_HELP,
+            Command::Stats   => STATS_HELP,
+            Command::Export  => EXPORT_HELP,
+        }
+    }
+}
+
+
+// the commands that write a file for every level
+const WRITERS: &[Command] = &[Command::Render, Command::Export];
+
+// every option the program knows: its short and long names, whether a value
+// follows it, and the commands it belongs to (none for every command)
+const OPTIONS: &[(Option<char>, &str, bool, &[Command])] = &[
+    (Some('h'), "help",          false, &[]),
+    (Some('v'), "version",       false, &[]),
+    (Some('V'), "verbose",       false, &[]),
+    (Some('g'), "game",          true,  &[]),
+    (None,      "map",           true,  &[]),
+    (None,      "report",        true,  &[]),
+    (Some('j'), "jobs",          true,  &[]),
+    (None,      "include",       true,  &[]),
+    (None,      "exclude",       true,  &[]),
+    (Some('t'), "transparent",   false, &[Command::Render]),
+    (Some('l'), "lighting",      false, &[Command::Render]),
+    (Some('i'), "invert",        false, &[Command::Render]),
+    (Some('s'), "size",          true,  &[Command::Render]),
+    (None,      "scale",         true,  &[Command::Render]),
+    (Some('d'), "doors",         false, &[Command::Render]),
+    (Some('c'), "specials",      false, &[Command::Render]),
+    (Some('a'), "automap",       false, &[Command::Render]),
+    (None,      "automap-start", false, &[Command::Render]),
+    (None,      "spoiler",       false, &[Command::Render]),
+    (Some('S'), "secrets",       false, &[Command::Render]),
+    (Some('z'), "hazards",       false, &[Command::Render]),
+    (Some('L'), "legend",        false, &[Command::Render]),
+    (None,      "crop-things",   false, &[Command::Render]),
+    (None,      "crop",          true,  &[Command::Render]),
+    (Some('r'), "rotate",        true,  &[Command::Render]),
+    (Some('m'), "mirror",        false, &[Command::Render]),
+    (None,      "padding",       true,  &[Command::Render]),
+    (Some('G'), "grid",          true,  &[Command::Render]),
+    (Some('x'), "axes",          false, &[Command::Render]),
+    (Some('O'), "optimize",      false, &[Command::Render]),
+    (Some('f'), "force",         false, &[Command::Render]),
+    (Some('w'), "watch",         false, &[Command::Render]),
+    (Some('o'), "output",        true,  WRITERS),
+    (None,      "name",          true,  WRITERS),
+    (None,      "if-exists",     true,  WRITERS),
+    (None,      "mirror-tree",   false, WRITERS),
+    (None,      "lumps",         false, &[Command::List]),
+    (None,      "levels",        false, &[Command::List]),
+    (None,      "lump",          true,  &[Command::Extract]),
+    (None,      "dir",           true,  &[Command::Extract]),
+    (None,      "json",          false, &[Command::Export]),
+    (None,      "compact",       false, &[Command::Export]),
+];
+
+
+type OptSpec = (Option<char>, &'static str, bool, &'static [Command]);
+
+
+fn find_long(name: &str) -> Option<&'static OptSpec> {
+    OPTIONS.iter().find(|o| o.1 == name)
+}
+
+
+fn find_short(c: char) -> Option<&'static OptSpec> {
+    OPTIONS.iter().find(|o| o.0 == Some(c))
+}
+
+
+// the number of single character edits between two words
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..(b.len() + 1)).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let above = row[j + 1];
+            row[j + 1] = min(min(row[j + 1] + 1, row[j] + 1), diag + (ca != b[j]) as usize);
+            diag = above;
+        }
+    }
+    row[b.len()]
+}
+
+
+// the error for an option nobody knows, suggesting the closest long option
+fn unknown_option(given: &str) -> String {
+    // a short option is one letter, so it's only ever close to a long name by chance
+    let name = match given.strip_prefix("--") {
+        Some(name) => name,
+        None       => { return format!("Err: Unknown option '{}', see --help for the options", given); }
+    };
+    let closest = OPTIONS
+        .iter()
+        .map(|o| (edit_distance(name, o.1), o.1))
+        .min();
+    match closest {
+        Some((d, long)) if d <= max(1, name.len() / 3) => {
+            format!("Err: Unknown option '{}', did you mean '--{}'?", given, long)
+        }
+        _ => format!("Err: Unknown option '{}', see --help for the options", given),
+    }
+}
+
+
+// read the value given to an option as a number
+fn number<T: FromStr>(long: &str, v: &str) -> Result<T, String> {
+    match v.parse::<T>() {
+        Ok(n) => Ok(n),
+        _     => Err(format!("Err: Couldn't parse '{}' to a number for --{}", v, long)),
+    }
+}
+
+
+/// How closely a render should follow the in-game automap
+#[derive(Clone, Copy, PartialEq)]
+pub enum Automap {
+    Off,      // draw every line as it is stored in the map
+    Explored, // the automap once every line has been seen
+    Start,    // the automap at level start with a computer area map
+    Spoiler,  // like Explored, but hidden and secret lines are dashed
+}
+
+
+impl Automap {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Automap::Off      => "full",
+            Automap::Explored => "automap",
+            Automap::Start    => "start",
+            Automap::Spoiler  => "spoiler",
+        }
+    }
+}
+
+
+/// What to do when a file about to be written is already there
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Existing {
+    Overwrite,
+    Skip,
+    Fail,
+}
+
+
+impl Existing {
+    pub fn from_name(name: &str) -> Option<Existing> {
+        match name {
+            "overwrite" => Some(Existing::Overwrite),
+            "skip"      => Some(Existing::Skip),
+            "fail"      => Some(Existing::Fail),
+            _           => None,
+        }
+    }
+}
+
+
+#[derive(Clone)]
 pub struct Options {
+    pub command:     Option<Command>, // None when no command was named
     pub help:        bool,
     pub files:       Vec<String>,
+    pub file_count:  usize, // the files worked on, once directories are searched
     pub version:     bool,
     pub verbose:     bool,
     pub lighting:    bool,
     pub inverted:    bool,
     pub target_size: u64,
+    pub scale:       Option<f64>,
+    pub crop_things: bool,
+    pub crop:        Option<(i32, i32, i32, i32)>,
+    pub rotate:      f64,
+    pub mirror:      bool,
+    pub padding:     u64,
     pub transparent: bool,
     pub color_doors: bool,
+    pub specials:    bool,
+    pub game:        Option<Game>,
+    pub maps:        Vec<MapFilter>,
+    pub report:      bool, // print a JSON summary when done
+    pub jobs:        usize,
+    pub automap:     Automap,
+    pub secrets:     bool,
+    pub hazards:     bool,
+    pub legend:      bool,
+    pub grid:        u64,
+    pub axes:        bool,
+    pub optimize:    bool,
+    pub output:      Option<String>,
+    pub template:    String,
+    pub existing:    Existing,
+    pub list_lumps:  bool,
+    pub list_levels: bool,
+    pub extract:     Vec<String>,
+    pub extract_dir: Option<String>,
+    pub compact:     bool,
+    pub force:       bool, // render levels the cache says haven't changed
+    pub watch:       bool,
+    pub includes:    Vec<String>, // globs a file found in a directory must match
+    pub excludes:    Vec<String>, // and mustn't
+    pub mirror_tree: bool,
+}
+
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            command:     None,
+            help:        false,
+            files:       Vec::new(),
+            file_count:  0,
+            version:     false,
+            verbose:     false,
+            lighting:    false,
+            inverted:    false,
+            target_size: 1024,
+            scale:       None,
+            crop_things: false,
+            crop:        None,
+            rotate:      0.0,
+            mirror:      false,
+            padding:     50,
+            transparent: false,
+            color_doors: false,
+            specials:    false,
+            game:        None,
+            maps:        Vec::new(),
+            report:      false,
+            jobs:        1,
+            automap:     Automap::Off,
+            secrets:     false,
+            hazards:     false,
+            legend:      false,
+            grid:        0,
+            axes:        false,
+            optimize:    false,
+            output:      None,
+            template:    String::from(DEFAULT_TEMPLATE),
+            existing:    Existing::Overwrite,
+            list_lumps:  false,
+            list_levels: false,
+            extract:     Vec::new(),
+            extract_dir: None,
+            compact:     false,
+            force:       false,
+            watch:       false,
+            includes:    Vec::new(),
+            excludes:    Vec::new(),
+            mirror_tree: false,
+        }
+    }
 }
 
 
@@ -49,97 +485,345 @@ impl Options {
         if arg_iter.len() == 0 {
             return Err(format!("No args supplied"));
         }
+        Options::from_args(arg_iter)
+    }
 
-        // all toggle-able fields for the Options struct
-        let mut help          = false;
-        let mut verbose       = false;
-        let mut version       = false;
-        let mut t_size : u64  = 1024; // TODO: this thingy
-        let mut transparent   = false;
-        let mut lighting      = false;
-        let mut color_doors   = false;
-        let mut inverted      = false;
-        let mut files_buf: Vec<String> = Vec::new();
-
-
-        // loop through all arguments and toggle options when detected
-        let length     : usize = arg_iter.len();
-        let mut index  : usize = 0;
-        while index < length {
-            // unpack the first argument into a local value
-            let v = match arg_iter.next() {
-                Some(arg) => arg,
-                None      => { return Err(format!("???")); },
-            };
-
-            match v.as_str() {
-                "-h"            => { help = true; }
-                "--help"        => { help = true; }
-                "-v"            => { version = true; }
-                "--version"     => { version = true; }
-                "-V"            => { verbose = true; }
-                "--verbose"     => { verbose = true; }
-                "-l"            => { lighting = true; }
-                "--lighting"    => { lighting = true; }
-                "-i"            => { inverted = true; }
-                "--invert"      => { inverted = true; }
-                "-d"            => { color_doors = true; }
-                "--doors"       => { color_doors = true; }
-                "-t"            => { transparent = true; }
-                "--transparent" => { transparent = true; }
-
-                // the next two options mirror eachother (no real way to work around this :s)
-                "-s"            => {
-                    let v2 = match arg_iter.next() {
-                        Some(arg) => arg,
-                        None      => { return Err(format!("No size arg supplied")); },
-                    };
-
-                    t_size = match v2.as_str().parse::<u64>() {
-                        Ok(i) => i,
-                        _     => { return Err(format!("Err: Couldn't parse '{}' to uint", v2)); }
-                    };
-                    index += 1;
+    // parse a list of arguments (without the binary path) into Options
+    // every argument is read before any is acted on, so a mistake anywhere
+    // is reported before any work starts
+    pub fn from_args<I>(args: I) -> Result<Options, String>
+        where I: IntoIterator<Item = String>
+    {
+        let args: Vec<String> = args.into_iter().collect();
+        let mut opts = Options::default();
+        let mut given: Vec<(&'static str, Option<String>)> = Vec::new();
+        let mut ended = false;
+        let mut index : usize = 0;
+
+        while index < args.len() {
+            let arg = &args[index];
+            index += 1;
+
+            // anything after "--" is a file, even if it looks like an option
+            if ended || !arg.starts_with('-') {
+                opts.positional(arg, ended);
+                continue;
+            }
+            if arg == "-" {
+                return Err(String::from("Err: WADs can't be read from stdin, give a file name instead of '-'"));
+            }
+            if arg == "--" {
+                ended = true;
+                continue;
+            }
+
+            // --name or --name=value
+            if let Some(rest) = arg.strip_prefix("--") {
+                let (name, inline) = match rest.find('=') {
+                    Some(eq) => (&rest[..eq], Some(rest[(eq + 1)..].to_owned())),
+                    None     => (rest, None),
+                };
+                let &(_, long, takes_value, _) = match find_long(name) {
+                    Some(o) => o,
+                    None    => { return Err(unknown_option(&format!("--{}", name))); }
+                };
+
+                let value = match (takes_value, inline) {
+                    (true, Some(v)) => Some(v),
+                    (true, None)    => match args.get(index) {
+                        Some(v) => { index += 1; Some(v.to_owned()) }
+                        None    => { return Err(format!("Err: --{} needs a value", long)); }
+                    },
+                    (false, Some(_)) => { return Err(format!("Err: --{} doesn't take a value", long)); }
+                    (false, None)    => None,
+                };
+                given.push((long, value));
+                continue;
+            }
+
+            // a bundle of short options like -tdi, where the last one can
+            // take a value either joined on (-s512) or as the next argument
+            let shorts: Vec<char> = arg[1..].chars().collect();
+            for (i, &c) in shorts.iter().enumerate() {
+                let &(_, long, takes_value, _) = match find_short(c) {
+                    Some(o) => o,
+                    None    => { return Err(unknown_option(&format!("-{}", c))); }
+                };
+                if !takes_value {
+                    given.push((long, None));
+                    continue;
                 }
-                "--size"        => {
-                    let v2 = match arg_iter.next() {
-                        Some(arg) => arg,
-                        None      => { return Err(format!("No size arg supplied")); },
-                    };
-                    
-                    t_size = match v2.as_str().parse::<u64>() {
-                        Ok(i) => i,
-                        _     => { return Err(format!("Err: Couldn't parse '{}' to uint", v2)); }
-                    };
-                    index += 1;
+
+                let joined: String = shorts[(i + 1)..].iter().collect();
+                let value = match joined.is_empty() {
+                    false => joined,
+                    true  => match args.get(index) {
+                        Some(v) => { index += 1; v.to_owned() }
+                        None    => { return Err(format!("Err: -{} needs a value", c)); }
+                    },
+                };
+                given.push((long, Some(value)));
+                break;
+            }
+        }
+
+        // options that belong to one command can't be given to another
+        let cmd = opts.command.unwrap_or(Command::Render);
+        for &(long, _) in &given {
+            if let Some(&(_, _, _, owners)) = find_long(long) {
+                if !owners.is_empty() && !owners.contains(&cmd) {
+                    let names: Vec<&str> = owners.iter().map(|c| c.name()).collect();
+                    return Err(format!(
+                        "Err: --{} is an option of '{}', not '{}'", long, names.join("' and '"), cmd.name(),
+                    ));
                 }
-                _               => { files_buf.push(v.to_string()); }
             }
+        }
 
-            index += 1;
+        for (long, value) in given {
+            opts.set(long, value.unwrap_or_default())?;
+        }
+        if opts.mirror_tree && opts.output.is_none() {
+            return Err(String::from("Err: --mirror-tree needs --output to mirror directories into"));
+        }
+        // what's written to stdout can't share it, or go into directories
+        if opts.to_stdout() && (opts.verbose || opts.report || opts.watch || opts.mirror_tree) {
+            return Err(String::from(
+                "Err: --output - can't be used with --verbose, --report, --watch or --mirror-tree",
+            ));
+        }
+        Ok(opts)
+    }
+
+    // whether --output - asked for what's written to go to stdout
+    pub fn to_stdout(&self) -> bool {
+        self.output.as_ref().map(|o| o == "-").unwrap_or(false)
+    }
+
+    // every option that changes how a map is drawn, as text for the render
+    // cache to hash; each field is named, so a new one won't build until
+    // it's sorted into one list or the other
+    pub fn render_key(&self) -> String {
+        let Options{
+            // what a map looks like
+            lighting, inverted, target_size, scale, crop_things, crop, rotate, mirror, padding,
+            transparent, color_doors, specials, game, automap, secrets, hazards, legend, grid,
+            axes, optimize,
+            // what's run, on which files, and where it goes
+            command: _, help: _, files: _, file_count: _, version: _, verbose: _, maps: _,
+            report: _, jobs: _, output: _, template: _, existing: _, list_lumps: _,
+            list_levels: _, extract: _, extract_dir: _, compact: _, force: _, watch: _,
+            includes: _, excludes: _, mirror_tree: _,
+        } = self;
+        format!(
+            "{} {} {} {:?} {} {:?} {} {} {} {} {} {} {:?} {} {} {} {} {} {} {}",
+            lighting, inverted, target_size, scale, crop_things, crop, rotate, mirror, padding,
+            transparent, color_doors, specials, game, automap.name(), secrets, hazards, legend, grid,
+            axes, optimize,
+        )
+    }
+
+    // a command can only be named before any file, or after "help"
+    fn positional(&mut self, arg: &str, ended: bool) {
+        if !ended && self.command.is_none() && self.files.is_empty() {
+            if arg == "help" && !self.help {
+                self.help = true;
+                return;
+            }
+            if let Some(c) = Command::from_name(arg) {
+                self.command = Some(c);
+                return;
+            }
         }
+        self.files.push(arg.to_owned());
+    }
 
-        Ok(Options {
-            help:        help,
-            files:       files_buf,
-            version:     version,
-            verbose:     verbose,
-            lighting:    lighting,
-            inverted:    inverted,
-            target_size: t_size,
-            transparent: transparent,
-            color_doors: color_doors,
-        })
+    // apply one option, given by its long name, with its value if it has one
+    // options that can be repeated add to a list, the rest keep the last value
+    fn set(&mut self, long: &str, v: String) -> Result<(), String> {
+        match long {
+            "help"          => { self.help = true; }
+            "version"       => { self.version = true; }
+            "verbose"       => { self.verbose = true; }
+            "lighting"      => { self.lighting = true; }
+            "invert"        => { self.inverted = true; }
+            "doors"         => { self.color_doors = true; }
+            "automap"       => { self.automap = Automap::Explored; }
+            "automap-start" => { self.automap = Automap::Start; }
+            "spoiler"       => { self.automap = Automap::Spoiler; }
+            "secrets"       => { self.secrets = true; }
+            "hazards"       => { self.hazards = true; }
+            "legend"        => { self.legend = true; }
+            "crop-things"   => { self.crop_things = true; }
+            "mirror"        => { self.mirror = true; }
+            "optimize"      => { self.optimize = true; }
+            "axes"          => { self.axes = true; }
+            "specials"      => { self.specials = true; }
+            "transparent"   => { self.transparent = true; }
+            "lumps"         => { self.list_lumps = true; }
+            "levels"        => { self.list_levels = true; }
+            "size"          => { self.target_size = number(long, &v)?; }
+            "padding"       => { self.padding = number(long, &v)?; }
+            "grid"          => { self.grid = number(long, &v)?; }
+            "jobs"          => {
+                self.jobs = number(long, &v)?;
+                if self.jobs == 0 {
+                    return Err(String::from("Err: --jobs needs at least one worker"));
+                }
+            }
+            "scale"         => {
+                self.scale = match v.parse::<f64>() {
+                    Ok(f) if f > 0.0 && f.is_finite() => Some(f),
+                    _ => { return Err(format!("Err: Couldn't parse '{}' to a positive scale", v)); }
+                };
+            }
+            "rotate"        => {
+                self.rotate = match v.parse::<f64>() {
+                    Ok(f) if f.is_finite() => f,
+                    _ => { return Err(format!("Err: Couldn't parse '{}' to an angle", v)); }
+                };
+            }
+            "crop"          => {
+                self.crop = match parse_region(&v) {
+                    Some(region) => Some(region),
+                    None         => { return Err(format!("Err: Couldn't parse '{}' to X1,Y1,X2,Y2", v)); }
+                };
+            }
+            "game"          => {
+                self.game = match Game::from_name(&v) {
+                    Some(g) => Some(g),
+                    None    => { return Err(format!("Err: Unknown game '{}'", v)); }
+                };
+            }
+            "map"           => {
+                for part in v.split(',') {
+                    self.maps.push(MapFilter::parse(part)?);
+                }
+            }
+            "report"        => {
+                match v.as_str() {
+                    "json" => { self.report = true; }
+                    _      => { return Err(format!("Err: Unknown report format '{}', only json is supported", v)); }
+                }
+            }
+            "output"        => { self.output = Some(v); }
+            "name"          => {
+                check_template(&v)?;
+                self.template = v;
+            }
+            "if-exists"     => {
+                self.existing = match Existing::from_name(&v) {
+                    Some(e) => e,
+                    None    => { return Err(format!("Err: Unknown policy '{}', use overwrite, skip or fail", v)); }
+                };
+            }
+            "lump"          => { self.extract.push(v.to_uppercase()); }
+            "dir"           => { self.extract_dir = Some(v); }
+            "json"          => {} // the only format export writes
+            "compact"       => { self.compact = true; }
+            "force"         => { self.force = true; }
+            "watch"         => { self.watch = true; }
+            "include"       => { self.includes.push(v); }
+            "exclude"       => { self.excludes.push(v); }
+            "mirror-tree"   => { self.mirror_tree = true; }
+            _               => { return Err(unknown_option(&format!("--{}", long))); }
+        }
+        Ok(())
     }
 
     // Print out the cargo version and pkg name
     pub fn print_version(&self) {
-        println!("{} version {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        say!("{} version {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     }
 
-    // print a help command when ran with -h
+    // print a help command when ran with -h, for the command if one was named
     pub fn print_help(&self) {
-        println!("{}", HELP_STR);
+        match self.command {
+            Some(c) => say!("{}", c.help()),
+            None    => say!("{}", HELP_STR),
+        }
+    }
+}
+
+
+// parse a region given as "x1,y1,x2,y2" into (min x, min y, max x, max y)
+// the corners can be given in any order
+fn parse_region(s: &str) -> Option<(i32, i32, i32, i32)> {
+    let nums: Vec<i32> = s
+        .split(',')
+        .map(|n| n.trim().parse::<i32>())
+        .collect::<Result<Vec<i32>, _>>()
+        .ok()?;
+    if nums.len() != 4 || nums[0] == nums[2] || nums[1] == nums[3] {
+        return None;
+    }
+    Some((
+        min(nums[0], nums[2]), min(nums[1], nums[3]),
+        max(nums[0], nums[2]), max(nums[1], nums[3]),
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use optparse::*;
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(parse_region("256,-64,0,128"), Some((0, -64, 256, 128)));
+        assert_eq!(parse_region("0,0,0,128"), None);
+        assert_eq!(parse_region("0,0,128"), None);
+        assert_eq!(parse_region("a,b,c,d"), None);
+    }
+
+    fn parse(line: &str) -> Result<Options, String> {
+        Options::from_args(line.split_whitespace().map(|a| a.to_owned()))
+    }
+
+    #[test]
+    fn test_option_forms() {
+        let opts = parse("--size=512 -tdi -r -45 -G64 doom.wad -- --odd.wad").unwrap();
+        assert_eq!(opts.target_size, 512);
+        assert!(opts.transparent && opts.color_doors && opts.inverted);
+        assert_eq!(opts.rotate, -45.0);
+        assert_eq!(opts.grid, 64);
+        assert_eq!(opts.files, vec!["doom.wad", "--odd.wad"]);
+
+        let opts = parse("-V list --map MAP01 --map=E1M*,MAP05 --levels doom.wad").unwrap();
+        assert_eq!(opts.command, Some(Command::List));
+        assert_eq!(opts.maps.len(), 3);
+        assert!(opts.verbose && opts.list_levels);
+
+        // only options that change the drawing change the render cache key
+        let key = parse("--size 512 doom.wad").unwrap().render_key();
+        assert_eq!(key, parse("-V -j 4 --force -s512 -o maps other.wad").unwrap().render_key());
+        assert_ne!(key, parse("--size 512 --legend doom.wad").unwrap().render_key());
+
+        let opts = parse("-o - --map MAP01 doom.wad").unwrap();
+        assert!(opts.to_stdout());
+        assert!(!parse("-o maps doom.wad").unwrap().to_stdout());
+    }
+
+    #[test]
+    fn test_option_errors() {
+        assert_eq!(
+            parse("--sise 512 doom.wad").err().unwrap(),
+            "Err: Unknown option '--sise', did you mean '--size'?"
+        );
+        assert_eq!(
+            parse("-q doom.wad").err().unwrap(),
+            "Err: Unknown option '-q', see --help for the options"
+        );
+        assert!(parse("-").is_err());
+        assert!(parse("-o - -V doom.wad").is_err());
+        assert!(parse("--output=- --report json doom.wad").is_err());
+        assert_eq!(parse("-- -").unwrap().files, vec!["-"]);
+        assert!(parse("--legend=yes doom.wad").is_err());
+        assert!(parse("doom.wad --size").is_err());
+        assert_eq!(
+            parse("info --lumps doom.wad").err().unwrap(),
+            "Err: --lumps is an option of 'list', not 'info'"
+        );
     }
 }
 
@@ -0,0 +1,143 @@
+// output.rs
+
+/// Where rendered files are written. Each file's path comes from a template
+/// filled in for the WAD and level it's for, placed under the output
+/// directory (the directory the WAD is in, unless --output says otherwise):
+///
+///   {file}  the WAD's file name, like doom2.wad
+///   {wad}   the WAD's file name without its extension, like doom2
+///   {map}   the level's name, like MAP01, made safe for a file name
+///   {mode}  how lines were drawn: full, automap, start or spoiler
+///   {ext}   the extension of the file type, like svg
+///
+/// The default template keeps the original layout, a '<wad>.maps'
+/// directory next to every WAD. Directories are made as they're needed.
+
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use optparse::{Options, Existing};
+
+
+pub const DEFAULT_TEMPLATE: &str = "{file}.maps/{map}.{ext}";
+
+
+// fill every {key} in a template with the value lookup gives for it
+fn fill<F>(template: &str, lookup: F) -> Result<String, String>
+    where F: Fn(&str) -> Option<String>
+{
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = match rest[open..].find('}') {
+            Some(c) => open + c,
+            None    => { return Err(format!("Err: Unclosed '{{' in template '{}'", template)); }
+        };
+        let key = &rest[(open + 1)..close];
+        match lookup(key) {
+            Some(value) => out.push_str(&value),
+            None        => { return Err(format!("Err: Unknown key '{{{}}}' in template '{}'", key, template)); }
+        }
+        rest = &rest[(close + 1)..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+
+// make sure a template only uses keys that can be filled in
+pub fn check_template(template: &str) -> Result<(), String> {
+    let known = ["file", "wad", "map", "mode", "ext"];
+    fill(template, |key| match known.contains(&key) {
+        true => Some(String::new()),
+        _    => None,
+    })?;
+    Ok(())
+}
+
+
+// lump names can hold characters that aren't allowed in file names
+pub fn lump_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            _ => c,
+        })
+        .collect()
+}
+
+
+// the path of the file to write for one level of a WAD
+pub fn output_path(fname: &str, map: &str, ext: &str, opts: &Options) -> Result<PathBuf, String> {
+    let wad = Path::new(fname);
+    let file = wad.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    let stem = wad.file_stem().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+
+    let name = fill(&opts.template, |key| match key {
+        "file" => Some(file.to_owned()),
+        "wad"  => Some(stem.to_owned()),
+        "map"  => Some(lump_file_name(map)),
+        "mode" => Some(opts.automap.name().to_owned()),
+        "ext"  => Some(ext.to_owned()),
+        _      => None,
+    })?;
+
+    let root = match opts.output {
+        Some(ref dir) => PathBuf::from(dir),
+        None          => wad.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+    };
+    Ok(root.join(name))
+}
+
+
+// get ready to write a file, following the policy for files already there
+// gives back false when the file should be left alone
+pub fn prepare(path: &Path, existing: Existing) -> Result<bool, String> {
+    if path.exists() {
+        match existing {
+            Existing::Overwrite => {}
+            Existing::Skip      => { return Ok(false); }
+            Existing::Fail      => { return Err(format!("'{}' already exists", path.display())); }
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            if let Err(why) = create_dir_all(dir) {
+                return Err(format!("Couldn't create '{}': {}", dir.display(), why));
+            }
+        }
+    }
+    Ok(true)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use output::*;
+
+    #[test]
+    fn test_fill_template() {
+        let lookup = |key: &str| match key {
+            "wad" => Some(String::from("doom2")),
+            "map" => Some(String::from("MAP01")),
+            _     => None,
+        };
+        assert_eq!(fill("{wad}/{map}.svg", lookup), Ok(String::from("doom2/MAP01.svg")));
+        assert!(fill("{wad}/{level}.svg", lookup).is_err());
+        assert!(fill("{wad/{map}.svg", lookup).is_err());
+
+        assert!(check_template(DEFAULT_TEMPLATE).is_ok());
+        assert!(check_template("{wad}/{map}-{mode}.{ext}").is_ok());
+        assert!(check_template("{name}.svg").is_err());
+    }
+
+    #[test]
+    fn test_map_name_is_file_safe() {
+        let opts = Options::default();
+        let path = output_path("wads/odd.wad", "E1/M1:*", "svg", &opts);
+        assert_eq!(path, Ok(PathBuf::from("wads/odd.wad.maps/E1_M1__.svg")));
+    }
+}
+
+// end
@@ -1,7 +1,8 @@
 // parse_wad.rs
 
-/// This file has one function dedicated to parsing a file
-/// It accepts a file path string and will return a Result<Wad, String>
+/// This file is dedicated to parsing a file
+/// read_wad_file() reads the header and lump directory of a Wad into a
+/// WadFile, and parse_wad() goes on to build a full Wad out of that
 
 use std::fs::File;
 use std::io::Read;
@@ -10,70 +11,129 @@ use utils::*;
 use optparse::Options;
 use doom::wad::{Wad, WadHeader};
 use doom::lump::Lump;
+use doom::game::Game;
 use doom::constants::{HEADER_WIDTH, LUMP_WIDTH};
 
-// Parse a wad file into a Wad struct
-pub fn parse_wad(fname: &str, opts: &Options) -> Result<Wad, String> {
+
+/// The raw pieces of a Wad file: its header, its lump directory and every
+/// byte of the file, for the commands that work on lumps rather than levels
+pub struct WadFile {
+    pub header: WadHeader,
+    pub lumps:  Vec<Lump>,
+    pub bytes:  Vec<u8>,
+}
+
+
+impl WadFile {
+    // the data a lump points at, if all of it lies inside the file
+    pub fn lump_data(&self, lump: &Lump) -> Option<&[u8]> {
+        if lump.size == 0 {
+            return Some(&[]);
+        }
+        if lump.posn < HEADER_WIDTH {
+            return None;
+        }
+        self.bytes.get(lump.posn..(lump.posn + lump.size))
+    }
+
+    // check if the levels are in Hexen format, which keeps its scripts in
+    // a BEHAVIOR lump; this is the map format, not the game
+    pub fn is_hexen(&self) -> bool {
+        self.lumps.iter().any(|l| l.name.starts_with("BEHAVIOR"))
+    }
+}
+
+
+// Read a wad file's header and lump directory
+pub fn read_wad_file(fname: &str, opts: &Options) -> Result<WadFile, String> {
     // open the file and read all the bytes into a local vector
     let mut f = match File::open(fname) {
         Ok(nf) => nf,
         _      => { return Err(String::from("Could not open up file")); }
     };
     let mut all_bytes: Vec<u8> = Vec::new();
-    match f.read_to_end(&mut all_bytes) {
-        Ok(_) => {}
-        _     => panic!("Failed to read all bytes from file"),
-    };
+    if let Err(why) = f.read_to_end(&mut all_bytes) {
+        return Err(format!("Failed to read '{}': {}", fname, why));
+    }
+
+    if all_bytes.len() < HEADER_WIDTH {
+        return Err(format!("File '{}' is not a WAD", &fname));
+    }
 
     // craft a new WAD header struct with 12 bytes
     let header = WadHeader::new(&all_bytes[0..HEADER_WIDTH]);
 
     if !header.is_wad() {
-        return Err(String::from(format!("File '{}' is not a WAD", &fname)));
+        // .pk3 and .zip files are zip archives, which can't be read yet
+        if all_bytes.starts_with(b"PK\x03\x04") {
+            return Err(format!("File '{}' is a zip archive, which can't be read yet", &fname));
+        }
+        return Err(format!("File '{}' is not a WAD", &fname));
     }
 
-    let data = &all_bytes[header.data_range()];
-    let lump_data = &all_bytes[header.lump_range()];
+    if header.lumpaddr < HEADER_WIDTH || header.lumpaddr > all_bytes.len() {
+        return Err(String::from("Lump directory lies outside of the file"));
+    }
 
-    let mut is_hexen = false;
+    let lump_data = &all_bytes[header.lump_range()];
 
     // create a new vector of Lumps from the infotable
     let mut lumps: Vec<Lump> = Vec::new();
 
     // loop through the info table to create lumps
     let mut offset: usize = 0;
-    while offset < lump_data.len() {
+    while offset + LUMP_WIDTH <= lump_data.len() && lumps.len() < header.numlumps {
         // slice the data into a packet
         let pkt = &lump_data[packet_range(offset, LUMP_WIDTH)];
 
         // add a new lump to the lump vector
-        let l = Lump::new(&pkt);
-
-        // check if we are in a Hexen Wad or not
-        // Hexen has a unique lump called BEHAVIOR
-        if l.name.starts_with("BEHAVIOR") {
-            is_hexen = true;
-        }
-        lumps.push(l);
+        lumps.push(Lump::new(&pkt));
 
         // bump the address by one packet width
         offset += LUMP_WIDTH;
     }
 
     if opts.verbose {
-        println!("Opened file {}", fname);
-        println!("Bytes read: {}", all_bytes.len());
+        say!("Opened file {}", fname);
+        say!("Bytes read: {}", all_bytes.len());
         header.print();
-        println!("Size of data pool: {}", data.len());
-        println!("Lump data size: {}", lump_data.len());
-        println!("Total lumps gathered: {}", lumps.len());
+        say!("Size of data pool: {}", header.data_range().len());
+        say!("Lump data size: {}", lump_data.len());
+        say!("Total lumps gathered: {}", lumps.len());
     }
 
     if lumps.len() != header.numlumps {
         return Err(String::from("Lump count does not match header"));
     }
 
-    return Wad::new(fname, header, &lumps, &data[..], is_hexen);
+    Ok(WadFile{ header: header, lumps: lumps, bytes: all_bytes })
+}
+
+
+// Parse a wad file into a Wad struct
+pub fn parse_wad(fname: &str, opts: &Options) -> Result<Wad, String> {
+    let file = read_wad_file(fname, opts)?;
+    build_wad(fname, file, opts)
+}
+
+
+// Build the levels of a Wad out of a file that has already been read
+pub fn build_wad(fname: &str, file: WadFile, opts: &Options) -> Result<Wad, String> {
+    let is_hexen = file.is_hexen();
+    let data = &file.bytes[file.header.data_range()];
+    let mut wad = Wad::new(fname, file.header, &file.lumps, data, is_hexen, Game::Doom)?;
+
+    // use the game given on the command line, or guess it from the lumps
+    // and the things in the levels, now they've been read
+    wad.game = match opts.game {
+        Some(g) => g,
+        None    => Game::detect(&file.lumps, &wad.levels),
+    };
+
+    if opts.verbose {
+        say!("Game: {}", wad.game.name());
+    }
+    Ok(wad)
 }
 
 // end
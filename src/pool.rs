@@ -0,0 +1,83 @@
+// pool.rs
+
+/// A pool of worker threads for jobs that don't depend on each other, like
+/// the files on the command line or the levels of a WAD. Results are handed
+/// back in the order the jobs were given, along with whatever each job
+/// printed, so a run reads the same however many workers it had.
+
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+use log::{capture, replay};
+
+
+// run work on every item with up to `jobs` threads, calling done with
+// each result on this thread, in the order of the items
+pub fn run<T, R, F, D>(jobs: usize, items: &[T], work: F, mut done: D)
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+    D: FnMut(&T, R),
+{
+    let workers = min(jobs, items.len());
+    if workers <= 1 {
+        for item in items {
+            let result = work(item);
+            done(item, result);
+        }
+        return;
+    }
+
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = channel();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let (tx, next, work) = (tx.clone(), &next, &work);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= items.len() {
+                    break;
+                }
+                if tx.send((i, capture(|| work(&items[i])))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // hold back results that finish early until the ones before are in
+        let mut waiting = BTreeMap::new();
+        let mut wanted = 0;
+        for (i, out) in rx {
+            waiting.insert(i, out);
+            while let Some((result, lines)) = waiting.remove(&wanted) {
+                replay(lines);
+                done(&items[wanted], result);
+                wanted += 1;
+            }
+        }
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pool::*;
+
+    #[test]
+    fn test_results_in_order() {
+        let items: Vec<u64> = (0..40).collect();
+        let mut seen = Vec::new();
+        run(8, &items, |&n| {
+            thread::sleep(::std::time::Duration::from_millis((40 - n) % 7));
+            n * n
+        }, |&n, sq| seen.push((n, sq)));
+        let expected: Vec<(u64, u64)> = items.iter().map(|&n| (n, n * n)).collect();
+        assert_eq!(seen, expected);
+    }
+}
+
+// end
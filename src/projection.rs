@@ -0,0 +1,255 @@
+// projection.rs
+
+/// A Projection is the coordinate math that places a level on the SVG
+/// viewbox. The region of the level being drawn is rotated and mirrored
+/// as asked, then shifted so its corner lands on the padding, and the Y
+/// axis is flipped since Doom's Y grows upwards while SVG's grows
+/// downwards. The map's lines and outlines, the grid and the axis labels
+/// are all placed through the same Projection, so they always line up.
+///
+/// Anything outside the region has no place on the viewbox, so lines and
+/// outlines are clipped to it (in level coordinates) before being mapped.
+
+pub struct Projection {
+    pub min_x:   i32, // the region of the level drawn, in level units
+    pub min_y:   i32,
+    pub max_x:   i32,
+    pub max_y:   i32,
+    pub angle:   f64, // counter-clockwise, in degrees
+    pub mirror:  bool,
+    pub width:   u64, // extent of the region once rotated
+    pub height:  u64,
+    pub padding: u64,
+    origin_x:    f64, // lowest rotated X and highest rotated Y
+    top_y:       f64,
+}
+
+
+impl Projection {
+    // build a projection for a level spanning the given bounds
+    pub fn new(min_x: i32, min_y: i32, max_x: i32, max_y: i32, padding: u64) -> Projection {
+        let mut p = Projection{
+            min_x:    min_x,
+            min_y:    min_y,
+            max_x:    max_x,
+            max_y:    max_y,
+            angle:    0.0,
+            mirror:   false,
+            width:    0,
+            height:   0,
+            padding:  padding,
+            origin_x: 0.0,
+            top_y:    0.0,
+        };
+        p.fit();
+        p
+    }
+
+    pub fn with_rotation(mut self, angle: f64) -> Projection {
+        self.angle = angle.rem_euclid(360.0);
+        self.fit();
+        self
+    }
+
+    // mirror the render left to right
+    pub fn with_mirror(mut self, mirror: bool) -> Projection {
+        self.mirror = mirror;
+        self.fit();
+        self
+    }
+
+    // the number of quarter turns the map is rotated by, if it's a whole number
+    pub fn quarter_turns(&self) -> Option<u32> {
+        match self.angle % 90.0 == 0.0 {
+            true => Some((self.angle / 90.0) as u32 % 4),
+            _    => None,
+        }
+    }
+
+    // cosine and sine of the angle, exact for right angles
+    fn cos_sin(&self) -> (f64, f64) {
+        match self.quarter_turns() {
+            Some(0) => (1.0, 0.0),
+            Some(1) => (0.0, 1.0),
+            Some(2) => (-1.0, 0.0),
+            Some(3) => (0.0, -1.0),
+            _       => {
+                let rad = self.angle.to_radians();
+                (rad.cos(), rad.sin())
+            }
+        }
+    }
+
+    // rotate then mirror a level coordinate
+    fn transform(&self, x: f64, y: f64) -> (f64, f64) {
+        let (cos, sin) = self.cos_sin();
+        let rx = x * cos - y * sin;
+        let ry = x * sin + y * cos;
+        match self.mirror {
+            true => (-rx, ry),
+            _    => (rx, ry),
+        }
+    }
+
+    // work out the extent of the region once it's rotated
+    fn fit(&mut self) {
+        let corners = [
+            (self.min_x, self.min_y), (self.max_x, self.min_y),
+            (self.min_x, self.max_y), (self.max_x, self.max_y),
+        ];
+        let points: Vec<(f64, f64)> = corners
+            .iter()
+            .map(|&(x, y)| self.transform(x as f64, y as f64))
+            .collect();
+
+        let lo_x = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+        let hi_x = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+        let lo_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let hi_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+
+        self.origin_x = lo_x;
+        self.top_y = hi_y;
+        self.width = (hi_x - lo_x).round() as u64;
+        self.height = (hi_y - lo_y).round() as u64;
+    }
+
+    // viewbox size of the map including the padding on both sides
+    pub fn view_width(&self) -> u64 {
+        self.width + 2 * self.padding
+    }
+
+    pub fn view_height(&self) -> u64 {
+        self.height + 2 * self.padding
+    }
+
+    // map a level coordinate inside the region onto the viewbox
+    pub fn to_view(&self, x: i32, y: i32) -> (f64, f64) {
+        let (rx, ry) = self.transform(x as f64, y as f64);
+        let vx = self.padding as f64 + rx - self.origin_x;
+        let vy = self.padding as f64 + self.top_y - ry;
+        (vx, vy)
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    // clip the line from a to b to the region (Liang-Barsky),
+    // returning nothing when none of it is inside
+    pub fn clip_line(&self, a: (i32, i32), b: (i32, i32)) -> Option<((i32, i32), (i32, i32))> {
+        if self.contains(a.0, a.1) && self.contains(b.0, b.1) {
+            return Some((a, b));
+        }
+
+        let (x, y) = (a.0 as f64, a.1 as f64);
+        let (dx, dy) = ((b.0 - a.0) as f64, (b.1 - a.1) as f64);
+        let edges = [
+            (-dx, x - self.min_x as f64),
+            (dx, self.max_x as f64 - x),
+            (-dy, y - self.min_y as f64),
+            (dy, self.max_y as f64 - y),
+        ];
+
+        let mut t0 = 0.0;
+        let mut t1 = 1.0;
+        for &(p, q) in edges.iter() {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = f64::max(t0, q / p);
+            } else {
+                t1 = f64::min(t1, q / p);
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+
+        let at = |t: f64| ((x + t * dx).round() as i32, (y + t * dy).round() as i32);
+        Some((at(t0), at(t1)))
+    }
+
+    // clip a closed outline to the region (Sutherland-Hodgman), cutting
+    // against each edge of the box in turn
+    pub fn clip_loop(&self, points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let (x1, y1, x2, y2) = (self.min_x, self.min_y, self.max_x, self.max_y);
+        let mut out: Vec<(i32, i32)> = points.to_vec();
+
+        // each edge keeps points on one side of a vertical or horizontal line
+        let edges: [(bool, i32, bool); 4] = [
+            (true, x1, true), (true, x2, false), (false, y1, true), (false, y2, false),
+        ];
+        for &(vertical, at, keep_above) in edges.iter() {
+            let inside = |p: (i32, i32)| {
+                let v = match vertical { true => p.0, _ => p.1 };
+                match keep_above { true => v >= at, _ => v <= at }
+            };
+            let cross = |p: (i32, i32), q: (i32, i32)| {
+                let (px, py, qx, qy) = (p.0 as f64, p.1 as f64, q.0 as f64, q.1 as f64);
+                match vertical {
+                    true  => (at, (py + (qy - py) * (at as f64 - px) / (qx - px)).round() as i32),
+                    _     => ((px + (qx - px) * (at as f64 - py) / (qy - py)).round() as i32, at),
+                }
+            };
+
+            let input = out;
+            out = Vec::new();
+            for (i, &p) in input.iter().enumerate() {
+                let prev = input[(i + input.len() - 1) % input.len()];
+                match (inside(prev), inside(p)) {
+                    (true, true)  => { out.push(p); }
+                    (true, false) => { out.push(cross(prev, p)); }
+                    (false, true) => { out.push(cross(prev, p)); out.push(p); }
+                    _             => {}
+                }
+            }
+        }
+        out
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use projection::*;
+
+    #[test]
+    fn test_to_view() {
+        let p = Projection::new(-64, 128, 960, 1152, 50);
+        assert_eq!(p.view_width(), 1124);
+        assert_eq!(p.to_view(-64, 1152), (50.0, 50.0));
+        assert_eq!(p.to_view(960, 128), (1074.0, 1074.0));
+        assert_eq!(p.to_view(300, 500), (414.0, 702.0));
+    }
+
+    #[test]
+    fn test_rotate_and_mirror() {
+        // a quarter turn puts the level's right edge along the top
+        let p = Projection::new(0, 0, 200, 100, 0).with_rotation(90.0);
+        assert_eq!((p.width, p.height), (100, 200));
+        assert_eq!(p.to_view(200, 0), (100.0, 0.0));
+
+        let p = Projection::new(0, 0, 200, 100, 0).with_mirror(true);
+        assert_eq!(p.to_view(0, 0), (200.0, 100.0));
+
+        let p = Projection::new(0, 0, 100, 100, 0).with_rotation(-45.0);
+        assert_eq!(p.quarter_turns(), None);
+        let (vx, vy) = p.to_view(30, 70);
+        assert_eq!((vx.round(), vy.round()), (71.0, 42.0));
+    }
+
+    #[test]
+    fn test_clipping() {
+        let p = Projection::new(0, 0, 100, 100, 50);
+        assert_eq!(p.clip_line((-50, 50), (150, 50)), Some(((0, 50), (100, 50))));
+        assert_eq!(p.clip_line((-50, -50), (-10, 150)), None);
+        assert_eq!(
+            p.clip_loop(&[(-50, -50), (50, -50), (50, 50), (-50, 50)]),
+            vec![(0, 0), (50, 0), (50, 50), (0, 50)]
+        );
+    }
+}
+
+// end
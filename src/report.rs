@@ -0,0 +1,82 @@
+// report.rs
+
+/// A Report records what happened to every file a run was given: the
+/// levels that were rendered, where each was written, and anything that
+/// went wrong. It can be printed as JSON with --report json for scripts
+/// and CI jobs to read.
+
+use json::Json;
+
+
+pub struct LevelReport {
+    pub name:    String,
+    pub output:  Option<String>, // where the level was written, if it was
+    pub skipped: bool,           // left alone because the file was there
+}
+
+
+pub struct FileReport {
+    pub file:   String,
+    pub levels: Vec<LevelReport>,
+    pub errors: Vec<String>,
+}
+
+
+impl FileReport {
+    pub fn new(file: &str) -> FileReport {
+        FileReport{ file: file.to_owned(), levels: Vec::new(), errors: Vec::new() }
+    }
+
+    pub fn add_level(&mut self, name: &str, output: Option<String>, skipped: bool) {
+        self.levels.push(LevelReport{ name: name.to_owned(), output: output, skipped: skipped });
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let levels: Vec<Json> = self.levels
+            .iter()
+            .map(|l| {
+                Json::object()
+                    .set("name", l.name.as_str())
+                    .set("output", l.output.clone())
+                    .set("skipped", l.skipped)
+            })
+            .collect();
+
+        Json::object()
+            .set("file", self.file.as_str())
+            .set("ok", self.is_ok())
+            .set("levels", levels)
+            .set("errors", self.errors.clone())
+    }
+}
+
+
+#[derive(Default)]
+pub struct Report {
+    pub files: Vec<FileReport>,
+}
+
+
+impl Report {
+    pub fn passed(&self) -> usize {
+        self.files.iter().filter(|f| f.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.files.len() - self.passed()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let files: Vec<Json> = self.files.iter().map(|f| f.to_json()).collect();
+        Json::object()
+            .set("files", files)
+            .set("passed", self.passed())
+            .set("failed", self.failed())
+    }
+}
+
+// end
@@ -5,161 +5,577 @@
 /// an object must implement the trait 'SVGObject' by which it
 /// should have a 'to_string()' method to convert it to it's XML
 /// representation in String format.
-
+///
+/// Coordinates are f64 so shapes can land between whole units, and every
+/// object can carry extra attributes (a class, an id, a transform or
+/// anything else) through the Attributes trait.
+///
+/// Documents are streamed out through a buffered writer rather than built
+/// up as one big string, so they can go to a file, stdout or memory.
+
+use std::fmt;
 use std::fs::File;
-use std::io::Write;
-use std::error::Error;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
 // utility for creating very basic colors for SVG writing
+#[derive(Clone, PartialEq)]
 pub enum Color {
-    Red, Blue, Green, Yellow, Black, White, Grey, None
+    Red, Blue, Green, Yellow, Black, White, Grey, None,
+    Orange, Purple, Cyan, Magenta, Brown, Lime, Maroon, Teal,
+    Pink, Olive,
+    Rgb(u8, u8, u8),
+    Pattern(String),
 }
 
 // convert a Color enum to a SVG string
 pub fn color_to_string(c: &Color) -> String {
     match *c {
-        Color::Red    => "red".to_owned(),
-        Color::None   => "none".to_owned(),
-        Color::Blue   => "blue".to_owned(),
-        Color::Grey   => "grey".to_owned(),
-        Color::Green  => "green".to_owned(),
-        Color::Black  => "black".to_owned(),
-        Color::White  => "white".to_owned(),
-        Color::Yellow => "yellow".to_owned(),
+        Color::Red     => "red".to_owned(),
+        Color::None    => "none".to_owned(),
+        Color::Blue    => "blue".to_owned(),
+        Color::Grey    => "grey".to_owned(),
+        Color::Green   => "green".to_owned(),
+        Color::Black   => "black".to_owned(),
+        Color::White   => "white".to_owned(),
+        Color::Yellow  => "yellow".to_owned(),
+        Color::Orange  => "orange".to_owned(),
+        Color::Purple  => "purple".to_owned(),
+        Color::Cyan    => "cyan".to_owned(),
+        Color::Magenta => "magenta".to_owned(),
+        Color::Brown   => "brown".to_owned(),
+        Color::Lime    => "lime".to_owned(),
+        Color::Maroon  => "maroon".to_owned(),
+        Color::Teal    => "teal".to_owned(),
+        Color::Pink    => "pink".to_owned(),
+        Color::Olive   => "olive".to_owned(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Pattern(ref id) => format!("url(#{})", id),
     }
 }
 
+// escape text so it can sit inside an element or an attribute value
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&'  => out.push_str("&amp;"),
+            '<'  => out.push_str("&lt;"),
+            '>'  => out.push_str("&gt;"),
+            '"'  => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _    => out.push(c),
+        }
+    }
+    out
+}
+
+// write a coordinate as briefly as possible: whole numbers lose their
+// decimal point, everything else is kept to two decimal places
+pub fn num(v: f64) -> String {
+    // adding zero turns -0 into 0
+    format!("{}", (v * 100.0).round() / 100.0 + 0.0)
+}
+
 // any SVG object we want to store in our SVG document should have a to_string() func
 pub trait SVGObject {
     fn to_string(&self) -> String;
+
+    // stream the object's XML out, objects holding others can override
+    // this to write their children one at a time
+    fn write_to(&self, out: &mut Write) -> io::Result<()> {
+        out.write_all(self.to_string().as_bytes())
+    }
+}
+
+
+// a transform applied to an object and everything inside it
+pub enum Transform {
+    Translate(f64, f64),
+    Rotate(f64),                // degrees clockwise about the origin
+    RotateAbout(f64, f64, f64), // degrees clockwise about a point
+    Scale(f64, f64),
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Transform::Translate(x, y)      => write!(f, "translate({} {})", num(x), num(y)),
+            Transform::Rotate(a)            => write!(f, "rotate({})", num(a)),
+            Transform::RotateAbout(a, x, y) => write!(f, "rotate({} {} {})", num(a), num(x), num(y)),
+            Transform::Scale(x, y)          => write!(f, "scale({} {})", num(x), num(y)),
+        }
+    }
+}
+
+
+// the attributes that paint an object rather than give its shape
+const PAINT: [&str; 5] = ["fill", "fill-opacity", "stroke", "stroke-width", "stroke-dasharray"];
+
+
+// extra attributes written out on an object, in the order they were set
+#[derive(Default)]
+pub struct AttrList {
+    pub list:    Vec<(String, String)>,
+    pub unpaint: bool, // leave the object's own paint to a stylesheet
+}
+
+impl AttrList {
+    // set an attribute, replacing any earlier value
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.list.iter_mut().find(|a| a.0 == name) {
+            Some(attr) => { attr.1 = value.to_owned(); }
+            None       => { self.list.push((name.to_owned(), value.to_owned())); }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.list.iter().find(|a| a.0 == name).map(|a| a.1.as_str())
+    }
+
+    // render every attribute, each with a leading space
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        for (name, value) in &self.list {
+            s.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        s
+    }
+
+    // render every attribute followed by an element's own, leaving out any
+    // of its own that one set here replaces, so no name is written twice
+    pub fn render_with(&self, own: &[(&str, String)]) -> String {
+        let mut s = self.render();
+        for (name, value) in own {
+            if self.get(name).is_none() && !(self.unpaint && PAINT.contains(name)) {
+                s.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+            }
+        }
+        s
+    }
+}
+
+
+// builder methods shared by every object that carries an AttrList
+pub trait Attributes: Sized {
+    fn attrs(&mut self) -> &mut AttrList;
+
+    fn with_attr(mut self, name: &str, value: &str) -> Self {
+        self.attrs().set(name, value);
+        self
+    }
+
+    fn with_class(self, class: &str) -> Self {
+        self.with_attr("class", class)
+    }
+
+    fn with_id(self, id: &str) -> Self {
+        self.with_attr("id", id)
+    }
+
+    // write only the object's shape, so its fill and stroke come from
+    // whatever stylesheet rules match it
+    fn styled(mut self) -> Self {
+        self.attrs().unpaint = true;
+        self
+    }
+
+    // transforms stack up in the order they're given
+    fn with_transform(mut self, t: Transform) -> Self {
+        let value = match self.attrs().get("transform") {
+            Some(prev) => format!("{} {}", prev, t),
+            None       => t.to_string(),
+        };
+        self.with_attr("transform", &value)
+    }
 }
 
+
 pub struct SVG {
     pub width:       u64,
     pub height:      u64,
     pub view_width:  u64,
     pub view_height: u64,
+    pub style:       Option<String>,
+    pub defs:        Vec<Box<SVGObject>>,
     pub objects:     Vec<Box<SVGObject>>,
 }
 
 pub struct SVGLine {
-    pub x1:     u64,
-    pub y1:     u64,
-    pub x2:     u64,
-    pub y2:     u64,
-    pub stroke: u64,
+    pub x1:     f64,
+    pub y1:     f64,
+    pub x2:     f64,
+    pub y2:     f64,
+    pub stroke: f64,
     pub color:  Color,
+    pub dash:   Option<f64>,
+    pub attrs:  AttrList,
 }
 
 pub struct SVGRect {
-    pub x:    u64,
-    pub y:    u64,
-    pub w:    u64,
-    pub h:    u64,
-    pub fill: Color,
+    pub x:     f64,
+    pub y:     f64,
+    pub w:     f64,
+    pub h:     f64,
+    pub fill:  Color,
+    pub attrs: AttrList,
 }
 
 pub struct SVGCircle {
-    pub cx:     u64,
-    pub cy:     u64,
-    pub radius: u64,
+    pub cx:     f64,
+    pub cy:     f64,
+    pub radius: f64,
+    pub fill:   Color,
+    pub stroke: Option<(Color, f64)>,
+    pub attrs:  AttrList,
+}
+
+pub struct SVGText {
+    pub x:     f64,
+    pub y:     f64,
+    pub size:  f64,
+    pub text:  String,
+    pub fill:  Color,
+    pub attrs: AttrList,
+}
+
+pub struct SVGPath {
+    pub d:       String,
+    pub fill:    Color,
+    pub opacity: f64,
+    pub stroke:  Option<(Color, f64)>,
+    pub attrs:   AttrList,
+}
+
+pub struct SVGHatch {
+    pub id:      String,
+    pub spacing: f64,
+    pub stroke:  f64,
+    pub color:   Color,
+}
+
+// a <g> element holding other objects, used to keep a drawing in layers
+// groups can hold other groups, and a transform on one moves all of it
+pub struct SVGGroup {
+    pub id:      String,
+    pub objects: Vec<Box<SVGObject>>,
+    pub attrs:   AttrList,
 }
 
 pub struct SVGVertex {
-    pub x: u64,
-    pub y: u64,
+    pub x: f64,
+    pub y: f64,
 }
 
+// an open polyline, or a polygon once closed
 pub struct SVGPoly {
     pub color:    Color,
-    pub stroke:   u64,
+    pub stroke:   f64,
+    pub fill:     Color,
+    pub closed:   bool,
     pub vertices: Vec<SVGVertex>,
+    pub attrs:    AttrList,
 }
 
 // implementations
 
 impl SVGLine {
     pub fn new(
-        x1: u64, y1: u64, x2: u64,
-        y2: u64, w: u64, color: Color
+        x1: f64, y1: f64, x2: f64,
+        y2: f64, w: f64, color: Color
     ) -> SVGLine {
-        SVGLine{x1: x1, y1: y1, x2: x2, y2: y2, stroke: w, color: color}
+        SVGLine{
+            x1: x1, y1: y1, x2: x2, y2: y2,
+            stroke: w, color: color, dash: None, attrs: AttrList::default(),
+        }
+    }
+
+    // a line with no paint of its own, for a stylesheet to stroke
+    pub fn shape(x1: f64, y1: f64, x2: f64, y2: f64) -> SVGLine {
+        SVGLine::new(x1, y1, x2, y2, 0.0, Color::None).styled()
+    }
+
+    // draw the line as dashes of the given length
+    pub fn dashed(mut self, len: f64) -> SVGLine {
+        self.dash = Some(len);
+        self
     }
 }
 
+impl Attributes for SVGLine {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
+
 // <line x1="50" y1="50" x2="200" y2="200" stroke="blue" stroke-width="4" />
 impl SVGObject for SVGLine {
     fn to_string(&self) -> String {
-        format!(
-            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" />",
-            self.x1, self.y1, self.x2, self.y2,
-            color_to_string(&self.color), self.stroke,
-        )
+        let mut own = vec![
+            ("x1", num(self.x1)), ("y1", num(self.y1)), ("x2", num(self.x2)), ("y2", num(self.y2)),
+            ("stroke", color_to_string(&self.color)), ("stroke-width", num(self.stroke)),
+        ];
+        if let Some(len) = self.dash {
+            own.push(("stroke-dasharray", num(len)));
+        }
+        format!("<line{} />", self.attrs.render_with(&own))
     }
 }
 
 // <rect x="25" y="25" width="200" height="200" fill="lime" stroke-width="4" stroke="pink" />
 impl SVGRect {
-    pub fn new(x: u64, y: u64, w: u64, h: u64, fill: Color) -> SVGRect {
-        SVGRect{x: x, y: y, w: w, h: h, fill: fill}
+    pub fn new(x: f64, y: f64, w: f64, h: f64, fill: Color) -> SVGRect {
+        SVGRect{x: x, y: y, w: w, h: h, fill: fill, attrs: AttrList::default()}
     }
 }
 
+impl Attributes for SVGRect {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
 
 impl SVGObject for SVGRect {
     fn to_string(&self) -> String {
-        format!(
-            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" />",
-            self.x, self.y, self.w, self.h,
-            color_to_string(&self.fill),
-        )
+        let own = [
+            ("x", num(self.x)), ("y", num(self.y)), ("width", num(self.w)), ("height", num(self.h)),
+            ("fill", color_to_string(&self.fill)),
+        ];
+        format!("<rect{} />", self.attrs.render_with(&own))
     }
 }
 
 
-// <circle cx="125" cy="125" r="75" fill="orange" />
+// <circle cx="125" cy="125" r="75" fill="orange" stroke="black" stroke-width="2" />
 impl SVGCircle {
-    pub fn new(cx: u64, cy: u64, r: u64) -> SVGCircle {
-        SVGCircle{cx: cx, cy: cy, radius: r}
+    pub fn new(cx: f64, cy: f64, r: f64, fill: Color) -> SVGCircle {
+        SVGCircle{cx: cx, cy: cy, radius: r, fill: fill, stroke: None, attrs: AttrList::default()}
+    }
+
+    pub fn with_stroke(mut self, color: Color, width: f64) -> SVGCircle {
+        self.stroke = Some((color, width));
+        self
     }
 }
 
+impl Attributes for SVGCircle {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
 
 impl SVGObject for SVGCircle {
+    fn to_string(&self) -> String {
+        let mut own = vec![
+            ("cx", num(self.cx)), ("cy", num(self.cy)), ("r", num(self.radius)),
+            ("fill", color_to_string(&self.fill)),
+        ];
+        own.extend(stroke_attrs(&self.stroke));
+        format!("<circle{} />", self.attrs.render_with(&own))
+    }
+}
+
+
+// the attributes of an optional stroke
+fn stroke_attrs(stroke: &Option<(Color, f64)>) -> Vec<(&'static str, String)> {
+    match *stroke {
+        Some((ref color, width)) => vec![("stroke", color_to_string(color)), ("stroke-width", num(width))],
+        None                     => vec![("stroke", String::from("none"))],
+    }
+}
+
+
+// <text x="20" y="35" font-size="12" fill="black">Hello</text>
+impl SVGText {
+    pub fn new(x: f64, y: f64, size: f64, text: &str, fill: Color) -> SVGText {
+        SVGText{x: x, y: y, size: size, text: text.to_owned(), fill: fill, attrs: AttrList::default()}
+    }
+
+    // text with no paint of its own, for a stylesheet to fill
+    pub fn shape(x: f64, y: f64, size: f64, text: &str) -> SVGText {
+        SVGText::new(x, y, size, text, Color::None).styled()
+    }
+}
+
+impl Attributes for SVGText {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
+
+impl SVGObject for SVGText {
+    fn to_string(&self) -> String {
+        let own = [
+            ("x", num(self.x)), ("y", num(self.y)), ("font-size", num(self.size)),
+            ("font-family", String::from("sans-serif")), ("fill", color_to_string(&self.fill)),
+        ];
+        format!("<text{}>{}</text>", self.attrs.render_with(&own), escape(&self.text))
+    }
+}
+
+
+// <path d="M 10 10 L 90 10 L 50 80 Z" fill="red" fill-rule="evenodd" />
+// paths are filled with the even-odd rule so that inner loops become holes
+impl SVGPath {
+    pub fn new(fill: Color) -> SVGPath {
+        SVGPath{d: String::new(), fill: fill, opacity: 1.0, stroke: None, attrs: AttrList::default()}
+    }
+
+    pub fn with_opacity(mut self, opacity: f64) -> SVGPath {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_stroke(mut self, color: Color, width: f64) -> SVGPath {
+        self.stroke = Some((color, width));
+        self
+    }
+
+    // use ready-made path data instead of building it up
+    pub fn with_data(mut self, d: &str) -> SVGPath {
+        self.d = d.to_owned();
+        self
+    }
+
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.d.push_str(&format!("M {} {} ", num(x), num(y)));
+    }
+
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.d.push_str(&format!("L {} {} ", num(x), num(y)));
+    }
+
+    pub fn close(&mut self) {
+        self.d.push_str("Z ");
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.d.is_empty()
+    }
+}
+
+impl Attributes for SVGPath {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
+
+impl SVGObject for SVGPath {
+    fn to_string(&self) -> String {
+        let mut own = vec![("d", self.d.trim_end().to_owned()), ("fill", color_to_string(&self.fill))];
+        if self.opacity != 1.0 {
+            own.push(("fill-opacity", num(self.opacity)));
+        }
+        own.push(("fill-rule", String::from("evenodd")));
+        own.extend(stroke_attrs(&self.stroke));
+        format!("<path{} />", self.attrs.render_with(&own))
+    }
+}
+
+
+// a pattern of diagonal stripes, referenced as a fill by Color::Pattern(id)
+impl SVGHatch {
+    pub fn new(id: &str, spacing: f64, stroke: f64, color: Color) -> SVGHatch {
+        SVGHatch{id: id.to_owned(), spacing: spacing, stroke: stroke, color: color}
+    }
+}
+
+
+impl SVGObject for SVGHatch {
     fn to_string(&self) -> String {
         format!(
-            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" />",
-            self.cx, self.cy, self.radius, "none"
+            "<pattern id=\"{}\" patternUnits=\"userSpaceOnUse\" width=\"{}\" height=\"{}\" patternTransform=\"rotate(45)\"><line x1=\"0\" y1=\"0\" x2=\"0\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" /></pattern>",
+            escape(&self.id), num(self.spacing), num(self.spacing), num(self.spacing),
+            escape(&color_to_string(&self.color)), num(self.stroke),
         )
     }
 }
 
 
+impl SVGGroup {
+    pub fn new(id: &str) -> SVGGroup {
+        SVGGroup{id: id.to_owned(), objects: Vec::new(), attrs: AttrList::default()}
+    }
+
+    pub fn add_object(&mut self, sobj: Box<SVGObject>) -> usize {
+        self.objects.push(sobj);
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Attributes for SVGGroup {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
+
+// <g id="walls"><line ... /></g>
+impl SVGObject for SVGGroup {
+    fn to_string(&self) -> String {
+        let mut s = format!("<g{}>", self.attrs.render_with(&[("id", self.id.clone())]));
+        for obj in &self.objects {
+            s.push_str(&obj.to_string());
+        }
+        s.push_str("</g>");
+        s
+    }
+
+    fn write_to(&self, out: &mut Write) -> io::Result<()> {
+        write!(out, "<g{}>", self.attrs.render_with(&[("id", self.id.clone())]))?;
+        for obj in &self.objects {
+            obj.write_to(out)?;
+        }
+        out.write_all(b"</g>")
+    }
+}
+
+
 impl SVGVertex {
-    pub fn new(x: u64, y: u64) -> SVGVertex {
+    pub fn new(x: f64, y: f64) -> SVGVertex {
         SVGVertex { x: x, y: y }
     }
 
     pub fn to_string(&self) -> String {
-        format!("{},{}", self.x, self.y)
+        format!("{},{}", num(self.x), num(self.y))
     }
 }
 
 
+// <polyline points="0,0 10,0 10,10" fill="none" stroke="black" stroke-width="2" />
 impl SVGPoly {
-    pub fn new(c: Color, stroke: u64) -> SVGPoly {
+    pub fn new(c: Color, stroke: f64) -> SVGPoly {
         let v: Vec<SVGVertex> = Vec::new();
-        SVGPoly{color: c, stroke: stroke, vertices: v}
+        SVGPoly{
+            color: c, stroke: stroke, fill: Color::None,
+            closed: false, vertices: v, attrs: AttrList::default(),
+        }
+    }
+
+    // join the last vertex back to the first, making a polygon
+    pub fn closed(mut self) -> SVGPoly {
+        self.closed = true;
+        self
+    }
+
+    pub fn with_fill(mut self, fill: Color) -> SVGPoly {
+        self.fill = fill;
+        self
     }
 
-    pub fn addv(&mut self, x: u64, y: u64) {
+    pub fn addv(&mut self, x: f64, y: f64) {
         self.vertices.push(SVGVertex::new(x, y));
     }
 }
 
+impl Attributes for SVGPoly {
+    fn attrs(&mut self) -> &mut AttrList { &mut self.attrs }
+}
+
 impl SVGObject for SVGPoly {
     fn to_string(&self) -> String {
-        String::from("not implemented")
+        let points: Vec<String> = self.vertices.iter().map(|v| v.to_string()).collect();
+        let own = [
+            ("points", points.join(" ")), ("fill", color_to_string(&self.fill)),
+            ("stroke", color_to_string(&self.color)), ("stroke-width", num(self.stroke)),
+        ];
+        format!(
+            "<{}{} />",
+            match self.closed { true => "polygon", _ => "polyline" },
+            self.attrs.render_with(&own),
+        )
     }
 }
 
@@ -173,6 +589,8 @@ impl SVG {
             height:      h,
             view_width:  vx,
             view_height: vy,
+            style:       None,
+            defs:        Vec::new(),
             objects:     Vec::new(),
         };
     }
@@ -183,41 +601,80 @@ impl SVG {
         return self.objects.len();
     }
 
-    // convert an SVG object to file format
-    pub fn to_file(&mut self, fname: &str) -> Result<u8, String> {
-        let head = format!(
+    // embed a stylesheet in the document
+    pub fn set_style(&mut self, css: &str) {
+        self.style = Some(css.to_owned());
+    }
+
+    // add rules to the end of the embedded stylesheet
+    pub fn add_style(&mut self, css: &str) {
+        match self.style {
+            Some(ref mut style) => style.push_str(css),
+            None                => self.style = Some(css.to_owned()),
+        }
+    }
+
+    // add a definition (like a pattern) that objects can refer to by id
+    pub fn add_def(&mut self, sobj: Box<SVGObject>) -> usize {
+        self.defs.push(sobj);
+        return self.defs.len();
+    }
+
+    // stream the whole document into any writer, buffering the many
+    // small writes and flushing at the end
+    pub fn write_to<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut w = BufWriter::new(out);
+        write!(
+            w,
             "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\">",
             self.width, self.height, self.view_width, self.view_height,
-        );
-        let tail = String::from("</svg>");
-        let mut buf: Vec<String> = Vec::new();
-
-        buf.push(head);
+        )?;
+        if let Some(ref css) = self.style {
+            write!(w, "<style>{}</style>", css)?;
+        }
+        if !self.defs.is_empty() {
+            w.write_all(b"<defs>")?;
+            for def in &self.defs {
+                def.write_to(&mut w)?;
+            }
+            w.write_all(b"</defs>")?;
+        }
         for obj in &self.objects {
-            buf.push(obj.to_string().to_owned());
+            obj.write_to(&mut w)?;
         }
-        buf.push(tail);
+        w.write_all(b"</svg>")?;
+        w.flush()
+    }
 
-        // open the file for writing
-        let mut f = match File::create(fname) {
+    // write the document out to a file, replacing anything already there
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<u8, String> {
+        let path = path.as_ref();
+        let f = match File::create(path) {
             Ok(new_file) => new_file,
             Err(why) => {
-                return Err(format!(
-                    "Couldn't create '{:?}': {}", fname, why.description()
-                ));
+                return Err(format!("Couldn't create '{}': {}", path.display(), why));
             }
         };
+        match self.write_to(f) {
+            Ok(_)    => Ok(0),
+            Err(why) => Err(format!("Couldn't write '{}': {}", path.display(), why)),
+        }
+    }
 
-        for stringthing in buf {
-            match f.write(stringthing.as_ref()) {
-                Ok(_) => {}
-                _ => {
-                    return Err(format!("Failed to write bytes to file"));
-                }
-            };
+    pub fn to_stdout(&self) -> Result<u8, String> {
+        let stdout = io::stdout();
+        match self.write_to(stdout.lock()) {
+            Ok(_)    => Ok(0),
+            Err(why) => Err(format!("Couldn't write to stdout: {}", why)),
         }
+    }
 
-        Ok(0)
+    // render the document in memory, for when it isn't going to disk
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        // writing into a Vec can't fail
+        self.write_to(&mut bytes).expect("write to memory");
+        bytes
     }
 }
 
@@ -230,9 +687,9 @@ mod tests {
 
         let mut s = SVG::new(1024, 1024, 1024, 1024);
 
-        let rect = SVGRect::new(0, 0, 1024, 1024, Color::White);
-        let line = SVGLine::new(0, 0, 1024, 1024, 5, Color::Black);
-        let line2 = SVGLine::new(1024, 0, 0, 1024, 10, Color::Black);
+        let rect = SVGRect::new(0.0, 0.0, 1024.0, 1024.0, Color::White);
+        let line = SVGLine::new(0.0, 0.0, 1024.0, 1024.0, 5.0, Color::Black);
+        let line2 = SVGLine::new(1024.0, 0.0, 0.0, 1024.0, 10.0, Color::Black);
 
         s.add_object(Box::new(rect));
         s.add_object(Box::new(line));
@@ -240,4 +697,87 @@ mod tests {
 
         s.to_file("test.svg");
     }
+
+    #[test]
+    fn test_group_classes() {
+        use svg::*;
+
+        let mut g = SVGGroup::new("walls");
+        g.add_object(Box::new(SVGLine::new(0.0, 0.0, 8.0, 8.0, 2.0, Color::Black).with_class("line one-sided")));
+        assert_eq!(
+            g.to_string(),
+            "<g id=\"walls\"><line class=\"line one-sided\" x1=\"0\" y1=\"0\" x2=\"8\" y2=\"8\" stroke=\"black\" stroke-width=\"2\" /></g>"
+        );
+    }
+
+    #[test]
+    fn test_text_escaping_and_transforms() {
+        use svg::*;
+
+        let text = SVGText::new(1.5, 2.25, 12.0, "Tom & Jerry's <lair>", Color::Black)
+            .with_transform(Transform::Translate(10.0, -0.004))
+            .with_transform(Transform::Rotate(90.0));
+        assert_eq!(
+            text.to_string(),
+            "<text transform=\"translate(10 0) rotate(90)\" x=\"1.5\" y=\"2.25\" font-size=\"12\" font-family=\"sans-serif\" fill=\"black\">Tom &amp; Jerry&apos;s &lt;lair&gt;</text>"
+        );
+    }
+
+    #[test]
+    fn test_no_repeated_attributes() {
+        use svg::*;
+
+        let g = SVGGroup::new("walls").with_id("outer");
+        assert_eq!(g.to_string(), "<g id=\"outer\"></g>");
+
+        let rect = SVGRect::new(0.0, 0.0, 4.0, 4.0, Color::Pattern(String::from("a\"b")))
+            .with_attr("fill", "none");
+        assert_eq!(rect.to_string(), "<rect fill=\"none\" x=\"0\" y=\"0\" width=\"4\" height=\"4\" />");
+
+        let rect = SVGRect::new(0.0, 0.0, 4.0, 4.0, Color::Pattern(String::from("a\"b")));
+        assert_eq!(rect.to_string(), "<rect x=\"0\" y=\"0\" width=\"4\" height=\"4\" fill=\"url(#a&quot;b)\" />");
+    }
+
+    #[test]
+    fn test_styled_objects_leave_out_paint() {
+        use svg::*;
+
+        let line = SVGLine::new(0.0, 0.0, 4.0, 4.0, 2.0, Color::Black).dashed(1.0).styled().with_class("wall");
+        assert_eq!(line.to_string(), "<line class=\"wall\" x1=\"0\" y1=\"0\" x2=\"4\" y2=\"4\" />");
+
+        // paint set as an extra attribute is still written
+        let text = SVGText::new(1.0, 2.0, 8.0, "E1M1", Color::Black).styled().with_attr("fill", "red");
+        assert_eq!(text.to_string(), "<text fill=\"red\" x=\"1\" y=\"2\" font-size=\"8\" font-family=\"sans-serif\">E1M1</text>");
+    }
+
+    #[test]
+    fn test_polygon() {
+        use svg::*;
+
+        let mut poly = SVGPoly::new(Color::Red, 1.0).closed().with_fill(Color::Blue);
+        poly.addv(0.0, 0.0);
+        poly.addv(10.0, 0.0);
+        poly.addv(5.0, 8.66);
+        assert_eq!(
+            poly.to_string(),
+            "<polygon points=\"0,0 10,0 5,8.66\" fill=\"blue\" stroke=\"red\" stroke-width=\"1\" />"
+        );
+    }
+
+    #[test]
+    fn test_write_to_memory() {
+        use svg::*;
+
+        let mut s = SVG::new(64, 32, 128, 64);
+        s.set_style(".line { stroke-linecap: round; }");
+        let mut g = SVGGroup::new("walls");
+        g.add_object(Box::new(SVGLine::new(0.0, 0.0, 8.0, 8.0, 2.0, Color::Black)));
+        s.add_object(Box::new(g));
+        assert_eq!(
+            String::from_utf8(s.to_bytes()).unwrap(),
+            "<svg width=\"64\" height=\"32\" viewBox=\"0 0 128 64\" xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\">\
+             <style>.line { stroke-linecap: round; }</style>\
+             <g id=\"walls\"><line x1=\"0\" y1=\"0\" x2=\"8\" y2=\"8\" stroke=\"black\" stroke-width=\"2\" /></g></svg>"
+        );
+    }
 }
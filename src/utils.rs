@@ -22,6 +22,13 @@ pub fn u8_to_i16(a: u8, b: u8) -> i16 {
     u8_to_u16(a, b) as i16
 }
 
+/// Convert a fixed-width, NUL-padded name (lump names, texture names)
+/// into a String, stopping at the first NUL byte
+pub fn u8_to_str(dat: &[u8]) -> String {
+    let end = dat.iter().position(|&c| c == 0).unwrap_or(dat.len());
+    String::from_utf8_lossy(&dat[..end]).to_string()
+}
+
 // testing section for byte conversions go here
 #[cfg(test)]
 mod tests {
@@ -41,6 +48,13 @@ mod tests {
         assert_eq!(unsigned_thing, signed_thing, "Conversion fail");
     }
 
+    #[test]
+    fn test_u8_to_str() {
+        assert_eq!(u8_to_str(b"FLAT20\0\0"), "FLAT20");
+        assert_eq!(u8_to_str(b"STARTAN3"), "STARTAN3");
+        assert_eq!(u8_to_str(b"\0\0\0\0\0\0\0\0"), "");
+    }
+
 }
 
 // end
@@ -0,0 +1,115 @@
+// watch.rs
+
+/// Watch mode, for mappers who keep a level open in an editor. Once the
+/// files have been rendered they're polled for changes to their size or
+/// modification time, and when one changes, the levels whose lumps are
+/// different are rendered again. Nothing that goes wrong stops the watch:
+/// it's printed, and the file is tried again the next time it changes.
+
+use std::collections::HashMap;
+use std::fs::metadata;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+use optparse::Options;
+use inputs::Input;
+use parse_wad::parse_wad;
+use mapfilter::select_levels;
+use mapmaker::make_maps;
+use report::FileReport;
+use doom::level::Level;
+
+
+// how long to wait between looks at the files
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+
+// a file's modification time and size, or None when it can't be read
+type Stamp = Option<(SystemTime, u64)>;
+
+
+fn stamp(fname: &str) -> Stamp {
+    let meta = metadata(fname).ok()?;
+    meta.modified().ok().map(|time| (time, meta.len()))
+}
+
+
+// a file being watched, and the hashes of its levels when last rendered
+struct Watched<'a> {
+    fname:   &'a str,
+    opts:    Options,
+    seen:    Stamp,
+    pending: Option<Stamp>, // a change that hasn't settled yet
+    levels:  HashMap<String, u64>,
+}
+
+
+impl<'a> Watched<'a> {
+    fn new(input: &'a Input, opts: &Options) -> Watched<'a> {
+        let fname = input.path.as_str();
+        let opts = input.options(opts);
+        let levels = match parse_wad(fname, &opts) {
+            Ok(wad) => wad.levels.iter().map(|l| (l.name.clone(), l.hash)).collect(),
+            _       => HashMap::new(),
+        };
+        Watched{ fname: fname, opts: opts, seen: stamp(fname), pending: None, levels: levels }
+    }
+
+    // look at the file again, and once a change has held still for a
+    // poll (so an editor has finished saving) render what it changed
+    fn poll(&mut self) {
+        let now = stamp(self.fname);
+        if now == self.seen {
+            self.pending = None;
+            return;
+        }
+        if self.pending != Some(now) {
+            self.pending = Some(now);
+            return;
+        }
+
+        self.seen = now;
+        self.pending = None;
+        if let Err(why) = self.render_changes() {
+            warn!("{}: {}", self.fname, why);
+        }
+    }
+
+    fn render_changes(&mut self) -> Result<u8, String> {
+        let wad = parse_wad(self.fname, &self.opts)?;
+        let changed: Vec<&Level> = select_levels(&wad.levels, &self.opts.maps, self.fname)
+            .into_iter()
+            .filter(|l| self.levels.get(&l.name) != Some(&l.hash))
+            .collect();
+
+        if changed.is_empty() {
+            say!("{} changed, but none of its levels did", self.fname);
+            return Ok(0);
+        }
+        let names: Vec<&str> = changed.iter().map(|l| l.name.as_str()).collect();
+        say!("{} changed, rendering {}", self.fname, names.join(", "));
+
+        // the levels are known to have changed, so the cache can't help
+        let mut forced = self.opts.clone();
+        forced.force = true;
+        make_maps(self.fname, &wad, &changed, &forced, &mut FileReport::new(self.fname))?;
+        for lev in changed {
+            self.levels.insert(lev.name.clone(), lev.hash);
+        }
+        Ok(0)
+    }
+}
+
+
+// watch every file given until the program is stopped
+pub fn watch(inputs: &[Input], opts: &Options) -> ! {
+    let mut files: Vec<Watched> = inputs.iter().map(|i| Watched::new(i, opts)).collect();
+    say!("Watching {} file(s) for changes, press Ctrl-C to stop", files.len());
+    loop {
+        sleep(POLL_INTERVAL);
+        for file in &mut files {
+            file.poll();
+        }
+    }
+}
+
+// end